@@ -0,0 +1,226 @@
+use std::io::{self, Write};
+
+use glam::Vec3;
+use serde_json::json;
+
+use crate::Mesh;
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const COMPONENT_FLOAT: u32 = 5126;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Floats per vertex in [`Mesh::vertex_data`]: position, normal and texcoord.
+const VERTEX_STRIDE: usize = 8;
+
+/// Writes meshes as a binary glTF (`.glb`) scene with one node.
+///
+/// Every `(name, mesh)` pair becomes a primitive with its own material named after the
+/// node, colored with `palette`; nodes for which `palette` returns `None` are left out.
+/// Meshes have no vertex colors, so no `COLOR_0` attribute is written and the material's
+/// base color is the only color of a primitive. Indexed meshes keep their indices.
+/// Luanti's coordinate system is left-handed, so Z is flipped (and triangles rewound) to
+/// fit glTF's right-handed one.
+pub fn export_gltf<'a>(
+    meshes: impl IntoIterator<Item = (&'a str, &'a Mesh)>,
+    mut palette: impl FnMut(&str) -> Option<[u8; 4]>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut materials = Vec::new();
+    let mut primitives = Vec::new();
+
+    for (name, mesh) in meshes {
+        if mesh.num_vertices() == 0 {
+            continue;
+        }
+
+        let Some(color) = palette(name) else {
+            continue;
+        };
+
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut indices = Vec::new();
+        let mut push_vertex = |vertex: &[f32]| {
+            positions.push(Vec3::new(vertex[0], vertex[1], -vertex[2]));
+            normals.push(Vec3::new(vertex[3], vertex[4], -vertex[5]));
+        };
+
+        if mesh.index_data().is_empty() {
+            for triangle in mesh.vertex_data().chunks_exact(VERTEX_STRIDE * 3) {
+                triangle
+                    .chunks_exact(VERTEX_STRIDE)
+                    .rev()
+                    .for_each(&mut push_vertex);
+            }
+        } else {
+            mesh.vertex_data()
+                .chunks_exact(VERTEX_STRIDE)
+                .for_each(push_vertex);
+            for triangle in mesh.index_data().chunks_exact(3) {
+                indices.extend(triangle.iter().rev());
+            }
+        }
+
+        let min = positions.iter().copied().reduce(Vec3::min).unwrap();
+        let max = positions.iter().copied().reduce(Vec3::max).unwrap();
+
+        let position_accessor = accessors.len();
+        push_vec3_view(&mut buffer, &mut buffer_views, &positions);
+        accessors.push(json!({
+            "bufferView": buffer_views.len() - 1,
+            "componentType": COMPONENT_FLOAT,
+            "count": positions.len(),
+            "type": "VEC3",
+            "min": min.to_array(),
+            "max": max.to_array(),
+        }));
+
+        let normal_accessor = accessors.len();
+        push_vec3_view(&mut buffer, &mut buffer_views, &normals);
+        accessors.push(json!({
+            "bufferView": buffer_views.len() - 1,
+            "componentType": COMPONENT_FLOAT,
+            "count": normals.len(),
+            "type": "VEC3",
+        }));
+
+        let mut primitive = json!({
+            "attributes": {
+                "POSITION": position_accessor,
+                "NORMAL": normal_accessor,
+            },
+            "material": materials.len(),
+        });
+
+        if !indices.is_empty() {
+            primitive["indices"] = json!(accessors.len());
+            push_index_view(&mut buffer, &mut buffer_views, &indices);
+            accessors.push(json!({
+                "bufferView": buffer_views.len() - 1,
+                "componentType": COMPONENT_UNSIGNED_INT,
+                "count": indices.len(),
+                "type": "SCALAR",
+            }));
+        }
+
+        primitives.push(primitive);
+
+        let [r, g, b, a] = color;
+        materials.push(json!({
+            "name": name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [
+                    srgb_to_linear(r),
+                    srgb_to_linear(g),
+                    srgb_to_linear(b),
+                    a as f32 / 255.0,
+                ],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+            "alphaMode": if a == 255 { "OPAQUE" } else { "BLEND" },
+        }));
+    }
+
+    let mut document = json!({
+        "asset": {
+            "version": "2.0",
+            "generator": "mesetools",
+        },
+        "scene": 0,
+        "scenes": [{ "nodes": [] }],
+        "nodes": [],
+    });
+
+    // glTF forbids meshes without primitives, as well as empty buffers
+    if !primitives.is_empty() {
+        document["scenes"][0]["nodes"] = json!([0]);
+        document["nodes"] = json!([{ "mesh": 0 }]);
+        document["meshes"] = json!([{ "primitives": primitives }]);
+        document["materials"] = json!(materials);
+        document["accessors"] = json!(accessors);
+        document["bufferViews"] = json!(buffer_views);
+        document["buffers"] = json!([{ "byteLength": buffer.len() }]);
+    }
+
+    let mut document = serde_json::to_vec(&document)?;
+    pad_to_4(&mut document, b' ');
+    pad_to_4(&mut buffer, 0);
+
+    let mut length = 12 + 8 + document.len();
+    if !buffer.is_empty() {
+        length += 8 + buffer.len();
+    }
+
+    writer.write_all(&GLB_MAGIC.to_le_bytes())?;
+    writer.write_all(&GLB_VERSION.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+
+    write_chunk(&mut writer, CHUNK_JSON, &document)?;
+    if !buffer.is_empty() {
+        write_chunk(&mut writer, CHUNK_BIN, &buffer)?;
+    }
+
+    Ok(())
+}
+
+fn push_vec3_view(buffer: &mut Vec<u8>, buffer_views: &mut Vec<serde_json::Value>, data: &[Vec3]) {
+    let offset = buffer.len();
+    for value in data {
+        for component in value.to_array() {
+            buffer.extend_from_slice(&component.to_le_bytes());
+        }
+    }
+
+    buffer_views.push(json!({
+        "buffer": 0,
+        "byteOffset": offset,
+        "byteLength": buffer.len() - offset,
+        "target": TARGET_ARRAY_BUFFER,
+    }));
+}
+
+fn push_index_view(buffer: &mut Vec<u8>, buffer_views: &mut Vec<serde_json::Value>, data: &[u32]) {
+    let offset = buffer.len();
+    for index in data {
+        buffer.extend_from_slice(&index.to_le_bytes());
+    }
+
+    buffer_views.push(json!({
+        "buffer": 0,
+        "byteOffset": offset,
+        "byteLength": buffer.len() - offset,
+        "target": TARGET_ELEMENT_ARRAY_BUFFER,
+    }));
+}
+
+fn write_chunk(writer: &mut impl Write, kind: u32, data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(&kind.to_le_bytes())?;
+    writer.write_all(data)
+}
+
+fn pad_to_4(data: &mut Vec<u8>, padding: u8) {
+    while !data.len().is_multiple_of(4) {
+        data.push(padding);
+    }
+}
+
+/// Palettes hold sRGB colors, while glTF material factors are linear.
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
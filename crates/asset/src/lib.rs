@@ -1,5 +1,11 @@
+mod gltf;
+mod vox;
+
 use glam::{Vec2, Vec3};
 
+pub use self::gltf::*;
+pub use self::vox::*;
+
 #[derive(Clone)]
 pub struct Vertex {
     pub position: Vec3,
@@ -27,10 +33,40 @@ impl Mesh {
         self.num_vertices += 1;
     }
 
+    /// Appends the vertices of `other`, moved by `offset`, along with its indices, which are
+    /// shifted to point at the appended vertices. Either both meshes are indexed or neither
+    /// is.
+    pub fn append(&mut self, other: &Mesh, offset: Vec3) {
+        debug_assert!(
+            self.num_vertices == 0
+                || other.num_vertices == 0
+                || (self.num_indices == 0) == (other.num_indices == 0),
+            "can't mix indexed and unindexed meshes"
+        );
+
+        for vertex in other.vertex_data.chunks_exact(8) {
+            self.vertex_data
+                .extend_from_slice(&(Vec3::from_slice(vertex) + offset).to_array());
+            self.vertex_data.extend_from_slice(&vertex[3..]);
+        }
+
+        let first_index = self.num_vertices;
+        self.index_data
+            .extend(other.index_data.iter().map(|index| first_index + index));
+        self.num_indices += other.num_indices;
+        self.num_vertices += other.num_vertices;
+    }
+
     pub fn vertex_data(&self) -> &[f32] {
         &self.vertex_data
     }
 
+    /// Indices into the vertices, three per triangle, or empty if the vertices are a plain
+    /// triangle list.
+    pub fn index_data(&self) -> &[u32] {
+        &self.index_data
+    }
+
     pub fn num_vertices(&self) -> u32 {
         self.num_vertices
     }
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use glam::ivec3;
+use world::Block;
+
+/// Largest palette a `.vox` file can hold. Index 0 is reserved for empty space.
+const MAX_COLORS: usize = 255;
+
+/// Writes a block as a MagicaVoxel `.vox` model.
+///
+/// `palette` gives the RGBA color of a node, or `None` to leave the node out (air). Each
+/// distinct node gets its own palette entry. MagicaVoxel models are Z-up, so the block's Y
+/// axis becomes the model's Z axis. A single block is well within the format's 256³ size
+/// limit; larger regions have to be split into several models.
+pub fn export_vox(
+    block: &Block,
+    mut palette: impl FnMut(&str) -> Option<[u8; 4]>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut colors = Vec::new();
+    let mut color_indices = HashMap::new();
+    let mut voxels = Vec::new();
+
+    for z in 0..16 {
+        for y in 0..16 {
+            for x in 0..16 {
+                let node = block.get_node(ivec3(x, y, z));
+                let Some(name) = block.get_name_by_id(node.id) else {
+                    continue;
+                };
+
+                let color_index = match color_indices.get(name) {
+                    Some(&index) => index,
+                    None => {
+                        let Some(color) = palette(name) else {
+                            continue;
+                        };
+
+                        if colors.len() == MAX_COLORS {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "more than 255 distinct nodes",
+                            ));
+                        }
+
+                        colors.push(color);
+                        let index = colors.len() as u8;
+                        color_indices.insert(name, index);
+                        index
+                    }
+                };
+
+                voxels.push([x as u8, z as u8, y as u8, color_index]);
+            }
+        }
+    }
+
+    let mut size = Vec::new();
+    for dim in [16i32; 3] {
+        size.extend_from_slice(&dim.to_le_bytes());
+    }
+
+    let mut xyzi = (voxels.len() as i32).to_le_bytes().to_vec();
+    xyzi.extend(voxels.iter().flatten());
+
+    let mut rgba = colors.concat();
+    rgba.resize(256 * 4, 0);
+
+    let mut main = Vec::new();
+    write_chunk(&mut main, b"SIZE", &size)?;
+    write_chunk(&mut main, b"XYZI", &xyzi)?;
+    write_chunk(&mut main, b"RGBA", &rgba)?;
+
+    writer.write_all(b"VOX ")?;
+    writer.write_all(&150i32.to_le_bytes())?;
+    writer.write_all(b"MAIN")?;
+    writer.write_all(&0i32.to_le_bytes())?;
+    writer.write_all(&(main.len() as i32).to_le_bytes())?;
+    writer.write_all(&main)?;
+
+    Ok(())
+}
+
+fn write_chunk(w: &mut impl Write, id: &[u8; 4], content: &[u8]) -> io::Result<()> {
+    w.write_all(id)?;
+    w.write_all(&(content.len() as i32).to_le_bytes())?;
+    w.write_all(&0i32.to_le_bytes())?;
+    w.write_all(content)
+}
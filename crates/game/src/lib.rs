@@ -0,0 +1,3 @@
+mod nodedef;
+
+pub use self::nodedef::*;
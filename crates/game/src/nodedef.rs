@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Node definitions of a game, keyed by node name.
+///
+/// Definitions live in the game's Lua code rather than in the map, so they are read from a
+/// `nodes.json` dump placed in the game directory. The dump is an object mapping node names
+/// to `{ "drawtype", "tiles", "paramtype2", "walkable" }`, the same fields as `minetest.registered_nodes`.
+/// Draw types and param2 types this crate doesn't know, e.g. from a newer engine, are read
+/// as `Unknown` rather than rejecting the whole dump.
+pub struct NodeDefs {
+    defs: HashMap<String, NodeDef>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NodeDefsError {
+    #[error("no node definition dump found at {0}")]
+    NotFound(PathBuf),
+
+    #[error("invalid node definitions: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NodeDef {
+    #[serde(default)]
+    pub drawtype: DrawType,
+
+    #[serde(default)]
+    pub tiles: Vec<Tile>,
+
+    #[serde(default)]
+    pub paramtype2: ParamType2,
+
+    /// Whether players collide with the node, as opposed to walking through it like a plant.
+    #[serde(default = "default_walkable")]
+    pub walkable: bool,
+}
+
+fn default_walkable() -> bool {
+    true
+}
+
+/// A texture of a node. Only the image name is kept; tile flags such as `backface_culling`
+/// are ignored.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "TileSpec")]
+pub struct Tile {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TileSpec {
+    Name(String),
+    Table {
+        #[serde(alias = "image")]
+        name: String,
+    },
+}
+
+impl From<TileSpec> for Tile {
+    fn from(spec: TileSpec) -> Self {
+        match spec {
+            TileSpec::Name(name) | TileSpec::Table { name } => Tile { name },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawType {
+    #[default]
+    Normal,
+    Airlike,
+    Liquid,
+    #[serde(rename = "flowingliquid")]
+    FlowingLiquid,
+    Glasslike,
+    GlasslikeFramed,
+    GlasslikeFramedOptional,
+    Allfaces,
+    AllfacesOptional,
+    Torchlike,
+    Signlike,
+    Plantlike,
+    PlantlikeRooted,
+    Firelike,
+    Fencelike,
+    Raillike,
+    Nodebox,
+    Mesh,
+    /// A draw type added after this list was written. Drawn like a see-through cube.
+    #[serde(other)]
+    Unknown,
+}
+
+/// How a node's param2 is interpreted.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType2 {
+    #[default]
+    None,
+    Flowingliquid,
+    Wallmounted,
+    Facedir,
+    #[serde(rename = "4dir")]
+    FourDir,
+    Leveled,
+    Degrotate,
+    Meshoptions,
+    Color,
+    Colorfacedir,
+    #[serde(rename = "color4dir")]
+    ColorFourDir,
+    Colorwallmounted,
+    Glasslikeliquidlevel,
+    Colordegrotate,
+    /// A param2 type added after this list was written. Its param2 is left undecoded.
+    #[serde(other)]
+    Unknown,
+}
+
+impl NodeDefs {
+    /// Definitions that know no nodes, so every node other than air is a full cube.
+    pub fn new() -> Self {
+        Self {
+            defs: HashMap::new(),
+        }
+    }
+
+    pub fn scan(game_path: impl AsRef<Path>) -> Result<Self, NodeDefsError> {
+        let path = game_path.as_ref().join("nodes.json");
+        if !path.is_file() {
+            return Err(NodeDefsError::NotFound(path));
+        }
+
+        let json = std::fs::read_to_string(path)?;
+
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, NodeDefsError> {
+        let defs = serde_json::from_str(json)?;
+
+        Ok(Self { defs })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NodeDef> {
+        self.defs.get(name)
+    }
+
+    /// Whether `name` hides the faces of the nodes next to it. Only `normal` nodes do: glass
+    /// and leaves are cubes that can be seen through, and plants don't fill their cell.
+    /// Nodes without a definition are assumed to be full cubes, except air and `ignore`.
+    pub fn is_face_solid(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(def) => def.drawtype == DrawType::Normal,
+            None => !matches!(name, "air" | "ignore"),
+        }
+    }
+
+    /// Whether `name` is drawn at all.
+    pub fn is_visible(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(def) => def.drawtype != DrawType::Airlike,
+            None => !matches!(name, "air" | "ignore"),
+        }
+    }
+
+    /// Whether players stand on `name` rather than falling through it. Nodes without a
+    /// definition are walkable, except air and `ignore`.
+    pub fn is_walkable(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(def) => def.walkable,
+            None => !matches!(name, "air" | "ignore"),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &NodeDef)> {
+        self.defs.iter().map(|(name, def)| (name.as_str(), def))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(json: &str) -> NodeDef {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let def = def("{}");
+
+        assert_eq!(def.drawtype, DrawType::Normal);
+        assert!(def.tiles.is_empty());
+        assert_eq!(def.paramtype2, ParamType2::None);
+        assert!(def.walkable);
+    }
+
+    #[test]
+    fn tiles_are_strings_or_tables() {
+        let def = def(r#"{ "tiles": [
+                "a.png",
+                { "name": "b.png", "backface_culling": false },
+                { "image": "c.png" }
+            ] }"#);
+
+        let names: Vec<_> = def.tiles.iter().map(|tile| tile.name.as_str()).collect();
+        assert_eq!(names, ["a.png", "b.png", "c.png"]);
+    }
+
+    #[test]
+    fn paramtype2_renames() {
+        assert_eq!(
+            def(r#"{ "paramtype2": "4dir" }"#).paramtype2,
+            ParamType2::FourDir
+        );
+        assert_eq!(
+            def(r#"{ "paramtype2": "color4dir" }"#).paramtype2,
+            ParamType2::ColorFourDir
+        );
+        assert_eq!(
+            def(r#"{ "drawtype": "flowingliquid" }"#).drawtype,
+            DrawType::FlowingLiquid
+        );
+    }
+
+    #[test]
+    fn unknown_types_dont_reject_the_dump() {
+        let defs = NodeDefs::from_json(
+            r#"{
+                "mod:new": { "drawtype": "hologram", "paramtype2": "spin" },
+                "mod:stone": {}
+            }"#,
+        )
+        .unwrap();
+
+        let new = defs.get("mod:new").unwrap();
+        assert_eq!(new.drawtype, DrawType::Unknown);
+        assert_eq!(new.paramtype2, ParamType2::Unknown);
+        assert!(defs.is_face_solid("mod:stone"));
+        assert!(!defs.is_face_solid("mod:new"));
+    }
+}
@@ -1,10 +1,37 @@
+use std::collections::HashMap;
+
 use asset::{Mesh, Vertex};
+use game::NodeDefs;
 use glam::{IVec3, Vec2, Vec3, ivec3, vec3};
 use world::Block;
 
-pub fn make_mesh(block: &Block) -> Mesh {
+/// Builds a mesh of the visible cube faces in `block`. A face is left out when the
+/// neighbouring node is solid according to `node_defs`, or is the same node, so the inner
+/// faces of glass walls and water bodies disappear too.
+pub fn make_mesh(block: &Block, node_defs: &NodeDefs) -> Mesh {
     let mut mesh = Mesh::new();
 
+    add_faces(block, node_defs, |_, vertex| mesh.add_vertex(vertex));
+
+    mesh
+}
+
+/// Like [`make_mesh`], but with a separate mesh for each node name, e.g. to give every
+/// node type its own material.
+pub fn make_meshes_by_node(block: &Block, node_defs: &NodeDefs) -> HashMap<String, Mesh> {
+    let mut meshes: HashMap<String, Mesh> = HashMap::new();
+
+    add_faces(block, node_defs, |name, vertex| {
+        meshes
+            .entry(name.to_owned())
+            .or_default()
+            .add_vertex(vertex);
+    });
+
+    meshes
+}
+
+fn add_faces(block: &Block, node_defs: &NodeDefs, mut add_vertex: impl FnMut(&str, Vertex)) {
     for z in 0..16 {
         for y in 0..16 {
             for x in 0..16 {
@@ -12,11 +39,11 @@ pub fn make_mesh(block: &Block) -> Mesh {
 
                 let name = block.get_name_by_id(block.get_node(pos).id).unwrap();
 
-                if name == "air" {
+                if !node_defs.is_visible(name) {
                     continue;
                 }
 
-                let contains_block = |pos: IVec3| {
+                let hides_face = |pos: IVec3| {
                     if pos.x < 0
                         || pos.y < 0
                         || pos.z < 0
@@ -27,32 +54,31 @@ pub fn make_mesh(block: &Block) -> Mesh {
                         return false;
                     }
 
-                    block.get_name_by_id(block.get_node(pos).id).unwrap() != "air"
+                    let neighbor = block.get_name_by_id(block.get_node(pos).id).unwrap();
+                    neighbor == name || node_defs.is_face_solid(neighbor)
                 };
 
                 let sides = [
-                    contains_block(pos + IVec3::X),
-                    contains_block(pos - IVec3::X),
-                    contains_block(pos + IVec3::Y),
-                    contains_block(pos - IVec3::Y),
-                    contains_block(pos + IVec3::Z),
-                    contains_block(pos - IVec3::Z),
+                    hides_face(pos + IVec3::X),
+                    hides_face(pos - IVec3::X),
+                    hides_face(pos + IVec3::Y),
+                    hides_face(pos - IVec3::Y),
+                    hides_face(pos + IVec3::Z),
+                    hides_face(pos - IVec3::Z),
                 ];
 
-                for (i, has_neighbor_cube) in sides.iter().enumerate() {
-                    if !*has_neighbor_cube {
+                for (i, hidden) in sides.iter().enumerate() {
+                    if !*hidden {
                         for vertex in &CUBE_FACES[i] {
                             let mut vertex = vertex.clone();
                             vertex.position += vec3(pos.x as f32, pos.y as f32, pos.z as f32);
-                            mesh.add_vertex(vertex.clone());
+                            add_vertex(name, vertex);
                         }
                     }
                 }
             }
         }
     }
-
-    mesh
 }
 
 const CUBE_FACES: [[Vertex; 6]; 6] = [
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+use glam::IVec3;
+
+use crate::{Block, Node};
+
+/// A single node changed by an edit, with enough information to reverse it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeChange {
+    /// Position of the node within its block.
+    pub pos: IVec3,
+    pub old: Node,
+    pub new: Node,
+}
+
+/// Undo and redo stacks for the node edits made to a block.
+///
+/// Only the changed nodes are recorded, not copies of the block. Once more than
+/// `max_depth` changes are recorded, the oldest ones can no longer be undone.
+pub struct EditHistory {
+    undo: VecDeque<NodeChange>,
+    redo: Vec<NodeChange>,
+    max_depth: usize,
+}
+
+impl EditHistory {
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_depth,
+        }
+    }
+
+    /// Sets the node at `pos` in `block` and records the change. Anything undone before is
+    /// no longer available to redo.
+    pub fn set_node(&mut self, block: &mut Block, pos: IVec3, node: Node) {
+        let old = block.get_node(pos);
+        if old == node {
+            return;
+        }
+
+        block.set_node(pos, node);
+
+        self.redo.clear();
+        self.undo.push_back(NodeChange {
+            pos,
+            old,
+            new: node,
+        });
+
+        if self.undo.len() > self.max_depth {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Reverts the most recent change, returning it, or `None` if there is nothing to undo.
+    pub fn undo(&mut self, block: &mut Block) -> Option<NodeChange> {
+        let change = self.undo.pop_back()?;
+        block.set_node(change.pos, change.old);
+        self.redo.push(change);
+
+        Some(change)
+    }
+
+    /// Applies the most recently undone change again, returning it, or `None` if there is
+    /// nothing to redo.
+    pub fn redo(&mut self, block: &mut Block) -> Option<NodeChange> {
+        let change = self.redo.pop()?;
+        block.set_node(change.pos, change.new);
+        self.undo.push_back(change);
+
+        Some(change)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+
+    fn node(id: u16) -> Node {
+        Node {
+            id,
+            param1: 0,
+            param2: 0,
+        }
+    }
+
+    fn block() -> Block {
+        let mut block = Block::new();
+        for name in ["air", "default:stone", "default:dirt", "default:sand"] {
+            block.get_or_insert_id(name);
+        }
+        block
+    }
+
+    #[test]
+    fn undo_and_redo_swap_the_node() {
+        let mut block = block();
+        let mut history = EditHistory::new(10);
+        let pos = ivec3(1, 2, 3);
+        let old = block.get_node(pos);
+
+        history.set_node(&mut block, pos, node(1));
+        assert_eq!(block.get_node(pos), node(1));
+
+        let change = history.undo(&mut block).unwrap();
+        assert_eq!(change.new, node(1));
+        assert_eq!(block.get_node(pos), old);
+        assert!(!history.can_undo());
+
+        history.redo(&mut block).unwrap();
+        assert_eq!(block.get_node(pos), node(1));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut block = block();
+        let mut history = EditHistory::new(10);
+
+        history.set_node(&mut block, ivec3(0, 0, 0), node(1));
+        history.undo(&mut block);
+        assert!(history.can_redo());
+
+        history.set_node(&mut block, ivec3(1, 0, 0), node(2));
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(&mut block), None);
+        assert_eq!(block.get_node(ivec3(0, 0, 0)), node(0));
+    }
+
+    #[test]
+    fn oldest_change_is_dropped_past_max_depth() {
+        let mut block = block();
+        let mut history = EditHistory::new(2);
+
+        for id in 1..=3 {
+            history.set_node(&mut block, ivec3(id as i32, 0, 0), node(id));
+        }
+
+        assert_eq!(history.undo(&mut block).unwrap().new, node(3));
+        assert_eq!(history.undo(&mut block).unwrap().new, node(2));
+        assert_eq!(history.undo(&mut block), None);
+        assert_eq!(block.get_node(ivec3(1, 0, 0)), node(1));
+    }
+
+    #[test]
+    fn zero_depth_keeps_nothing() {
+        let mut block = block();
+        let mut history = EditHistory::new(0);
+
+        history.set_node(&mut block, ivec3(0, 0, 0), node(1));
+
+        assert_eq!(block.get_node(ivec3(0, 0, 0)), node(1));
+        assert!(!history.can_undo());
+        assert_eq!(history.undo(&mut block), None);
+    }
+}
@@ -0,0 +1,35 @@
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+
+/// Every name interned so far. Names are never removed, which is fine for node names: a
+/// world only uses a few thousand of them at most.
+static NAMES: LazyLock<Mutex<HashSet<Arc<str>>>> = LazyLock::new(Default::default);
+
+/// Returns a shared copy of `name`. Blocks keep their node names this way, so a name
+/// used by thousands of blocks is stored once.
+pub fn intern(name: &str) -> Arc<str> {
+    let mut names = NAMES.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(interned) = names.get(name) {
+        return interned.clone();
+    }
+
+    let interned: Arc<str> = Arc::from(name);
+    names.insert(interned.clone());
+
+    interned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_names_share_storage() {
+        let a = intern("default:stone");
+        let b = intern(&String::from("default:stone"));
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &intern("default:dirt")));
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+use glam::IVec3;
+
+use crate::{Area, MapBackend, MapError, decode_block_pos, encode_block_pos};
+
+/// The `leveldb` map backend, stored in a `map.db` directory.
+///
+/// Only a stub so far: it can't read anything, as reading LevelDB's table files is not
+/// implemented, and every method fails with [`MapError::Unsupported`]. For the same reason
+/// [`open_map`](crate::open_map) doesn't use it. What is real are the keys blocks are
+/// stored under, [`leveldb_key`] and [`parse_leveldb_key`].
+pub struct LevelDbBackend {
+    path: PathBuf,
+}
+
+impl LevelDbBackend {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no LevelDB database at {}", path.display()),
+            )
+            .into());
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn unsupported<T>(&self) -> Result<T, MapError> {
+        Err(MapError::Unsupported(format!(
+            "reading the LevelDB map at {}",
+            self.path.display()
+        )))
+    }
+}
+
+impl MapBackend for LevelDbBackend {
+    fn get_block_data(&mut self, _pos: IVec3) -> Result<Vec<u8>, MapError> {
+        self.unsupported()
+    }
+
+    fn bounds(&mut self) -> Result<Option<Area>, MapError> {
+        self.unsupported()
+    }
+
+    fn block_positions(&mut self) -> Result<Vec<IVec3>, MapError> {
+        self.unsupported()
+    }
+
+    fn set_blocks_data(&mut self, _blocks: &[(IVec3, Vec<u8>)]) -> Result<(), MapError> {
+        self.unsupported()
+    }
+
+    fn block_count(&mut self) -> Result<u64, MapError> {
+        self.unsupported()
+    }
+}
+
+/// The key a block is stored under: its [`encode_block_pos`] integer in decimal, as written
+/// by Luanti.
+pub fn leveldb_key(pos: IVec3) -> Option<String> {
+    encode_block_pos(pos).map(|key| key.to_string())
+}
+
+/// Inverse of [`leveldb_key`]. Returns `None` for keys that aren't block positions.
+pub fn parse_leveldb_key(key: &str) -> Option<IVec3> {
+    decode_block_pos(key.parse().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+
+    #[test]
+    fn keys_round_trip() {
+        assert_eq!(leveldb_key(ivec3(1, 2, 3)).as_deref(), Some("50339841"));
+        assert_eq!(leveldb_key(ivec3(-1, 0, 0)).as_deref(), Some("-1"));
+        assert_eq!(leveldb_key(ivec3(4096, 0, 0)), None);
+
+        assert_eq!(parse_leveldb_key("-201306142"), Some(ivec3(-30, 5, -12)));
+        assert_eq!(parse_leveldb_key("not a key"), None);
+    }
+}
@@ -1,11 +1,29 @@
+mod area;
+mod edit;
+mod intern;
+#[cfg(feature = "leveldb")]
+mod leveldb;
 mod map;
+#[cfg(any(test, feature = "testing"))]
+mod memory;
 mod meta;
+mod metadata;
+mod param2;
 mod sqlite;
 
 use std::path::{Path, PathBuf};
 
+pub use self::area::*;
+pub use self::edit::*;
+pub use self::intern::*;
+#[cfg(feature = "leveldb")]
+pub use self::leveldb::*;
 pub use self::map::*;
+#[cfg(any(test, feature = "testing"))]
+pub use self::memory::*;
 pub use self::meta::*;
+pub use self::metadata::*;
+pub use self::param2::*;
 pub use self::sqlite::*;
 
 pub struct World {
@@ -25,6 +43,10 @@ pub enum Error {
     #[error("unknown map backend: {0}")]
     UnknownBackend(String),
 
+    /// A backend the game has, but this crate can't read.
+    #[error("unsupported map backend: {0}")]
+    UnsupportedBackend(String),
+
     #[error("invalid path: {0}")]
     InvalidPath(PathBuf),
 }
@@ -43,19 +65,36 @@ impl World {
 
         let meta_path = path.join("world.mt");
         let meta = WorldMeta::open(meta_path)?;
-        let backend = meta.get_str("backend").unwrap();
-
-        let map = match backend {
-            "sqlite3" => {
-                let sqlite_path = path.join("map.sqlite");
-                let sqlite = SqliteBackend::new(sqlite_path)?;
-                Map::new(sqlite)
-            }
-            _ => {
-                return Err(Error::UnknownBackend(backend.to_owned()));
-            }
-        };
+        let map = open_map(path, &meta)?;
 
         Ok(Self { name, meta, map })
     }
 }
+
+/// Opens the map of the world at `world_path` with the backend named in its `world.mt`.
+///
+/// The map file is looked up in the world directory under the backend's default name,
+/// unless `world.mt` sets `map_path` to the file itself or `map_directory` to the directory
+/// holding it. Relative paths are resolved against the world directory.
+///
+/// Only SQLite maps can be read. The other backends of the game, LevelDB included, give
+/// [`Error::UnsupportedBackend`].
+pub fn open_map(world_path: impl AsRef<Path>, meta: &WorldMeta) -> Result<Map, Error> {
+    let world_path = world_path.as_ref();
+
+    let map_path =
+        |default_name: &str| match (meta.get_str("map_path"), meta.get_str("map_directory")) {
+            (Some(path), _) => world_path.join(path),
+            (None, Some(directory)) => world_path.join(directory).join(default_name),
+            (None, None) => world_path.join(default_name),
+        };
+
+    match meta.parsed().backend {
+        Backend::Sqlite => {
+            let sqlite = SqliteBackend::new(map_path("map.sqlite"))?;
+            Ok(Map::new(sqlite))
+        }
+        Backend::Other(name) if name != "dummy" => Err(Error::UnknownBackend(name)),
+        backend => Err(Error::UnsupportedBackend(backend.name().to_owned())),
+    }
+}
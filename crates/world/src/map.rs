@@ -1,18 +1,46 @@
 use std::{
-    collections::HashMap,
-    io::{Cursor, Read},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{BufRead, Cursor, ErrorKind, Read, Write},
+    num::NonZeroUsize,
     string::FromUtf8Error,
-    sync::Mutex,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
 };
 
+use flate2::bufread::ZlibDecoder;
+use game::NodeDef;
 use glam::IVec3;
+use lru::LruCache;
+use rayon::prelude::*;
+use zstd::stream::raw::{DParameter, Decoder, InBuffer, Operation, OutBuffer};
+
+use crate::metadata::read_metadata_list;
+use crate::{Area, NodeMetadata, Param2, intern};
 
-// TODO: split this
 #[derive(thiserror::Error, Debug)]
 pub enum MapError {
     #[error("block not found")]
     BlockNotFound,
 
+    #[error("invalid block data: {0}")]
+    Parse(#[from] ParseError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("block position {0} can't be stored in this map")]
+    PosOutOfRange(IVec3),
+
+    #[error("not supported yet: {0}")]
+    Unsupported(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseError {
     #[error("unsupported block version: {0}")]
     UnsupportedVersion(u8),
 
@@ -22,140 +50,1296 @@ pub enum MapError {
     #[error("invalid utf-8: {0}")]
     InvalidUtf8(#[from] FromUtf8Error),
 
+    #[error("zstd decompression failed: {0}")]
+    Decompression(std::io::Error),
+
+    #[error("zlib decompression failed: {0}")]
+    ZlibDecompression(std::io::Error),
+
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
-
-    #[error("sqlite error: {0}")]
-    Sqlite(#[from] rusqlite::Error),
 }
 
 pub struct Map {
     backend: Mutex<Box<dyn MapBackend>>,
+    /// Recently parsed blocks. Missing blocks and parse errors aren't cached.
+    cache: Option<Mutex<LruCache<IVec3, Arc<Block>>>>,
 }
 
 impl Map {
+    /// Number of parsed blocks [`Map::new`] keeps around, about 4 MiB worth.
+    pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
     pub fn new(backend: impl MapBackend) -> Self {
+        Self::with_cache_capacity(backend, Self::DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Creates a map that keeps up to `capacity` parsed blocks, dropping the least recently
+    /// used ones first. A capacity of 0 turns the cache off.
+    ///
+    /// The cache assumes nothing else writes to the map while it is open. Writes through
+    /// [`Map::set_blocks_data`] drop the blocks they replace.
+    pub fn with_cache_capacity(backend: impl MapBackend, capacity: usize) -> Self {
         Self {
             backend: Mutex::new(Box::new(backend)),
+            cache: NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity))),
         }
     }
 
-    pub fn get_block(&self, pos: IVec3) -> Result<Block, MapError> {
-        let data = self.backend.lock().unwrap().get_block_data(pos)?;
-        Block::parse_data(&data)
+    pub fn get_block(&self, pos: IVec3) -> Result<Arc<Block>, MapError> {
+        if let Some(block) = self.with_cache(|cache| cache.get(&pos).cloned()).flatten() {
+            return Ok(block);
+        }
+
+        let data = self.backend().get_block_data(pos)?;
+        let block = Arc::new(Block::parse_data(&data)?);
+        self.with_cache(|cache| cache.put(pos, block.clone()));
+
+        Ok(block)
+    }
+
+    /// Loads the blocks at `positions` with as few backend queries as possible, in the order
+    /// given, repeated positions included. Positions without any stored data are skipped,
+    /// and blocks that fail to parse come with their error.
+    pub fn get_blocks(&self, positions: &[IVec3]) -> Result<Vec<LoadedBlock>, MapError> {
+        let mut parsed = HashMap::new();
+        let mut missing = HashSet::new();
+
+        for &pos in positions {
+            match self.with_cache(|cache| cache.get(&pos).cloned()).flatten() {
+                Some(block) => {
+                    parsed.insert(pos, block);
+                }
+                None => {
+                    missing.insert(pos);
+                }
+            }
+        }
+
+        // Parse errors can't be cloned, so blocks that fail to parse keep their data and are
+        // parsed again for each time their position is repeated.
+        let mut unparsable = HashMap::new();
+        let missing: Vec<_> = missing.into_iter().collect();
+
+        for (pos, data) in self.backend().get_blocks_data(&missing)? {
+            match Block::parse_data(&data) {
+                Ok(block) => {
+                    let block = Arc::new(block);
+                    self.with_cache(|cache| cache.put(pos, block.clone()));
+                    parsed.insert(pos, block);
+                }
+                Err(_) => {
+                    unparsable.insert(pos, data);
+                }
+            }
+        }
+
+        let blocks = positions
+            .iter()
+            .filter_map(|pos| {
+                let block = match parsed.get(pos) {
+                    Some(block) => Ok(block.clone()),
+                    None => Block::parse_data(unparsable.get(pos)?).map(Arc::new),
+                };
+                Some((*pos, block))
+            })
+            .collect();
+
+        Ok(blocks)
+    }
+
+    /// Loads the blocks at `positions` into the cache ahead of time, e.g. along a tour, so
+    /// that [`Map::get_block`] finds them there later. The data is read in one backend query
+    /// and parsed on the rayon thread pool.
+    ///
+    /// Blocks beyond the cache's capacity push out the ones before them, so long paths are
+    /// best prefetched a stretch at a time. Missing blocks and parse errors are left for
+    /// `get_block` to report. Without a cache, this does nothing.
+    pub fn prefetch(&self, positions: &[IVec3]) -> Result<(), MapError> {
+        let Some(missing) = self.with_cache(|cache| {
+            let mut seen = HashSet::new();
+            positions
+                .iter()
+                .copied()
+                .filter(|pos| !cache.contains(pos) && seen.insert(*pos))
+                .collect::<Vec<_>>()
+        }) else {
+            return Ok(());
+        };
+
+        let data = self.backend().get_blocks_data(&missing)?;
+        let blocks: Vec<_> = data
+            .into_par_iter()
+            .filter_map(|(pos, data)| Some((pos, Arc::new(Block::parse_data(&data).ok()?))))
+            .collect();
+
+        self.with_cache(|cache| {
+            for (pos, block) in blocks {
+                cache.put(pos, block);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Tells whether the map stores a block at `pos`, without reading or parsing it.
+    pub fn block_exists(&self, pos: IVec3) -> Result<bool, MapError> {
+        self.backend().has_block(pos)
+    }
+
+    /// Returns the block at `pos` as stored, without parsing it.
+    pub fn get_block_data(&self, pos: IVec3) -> Result<Vec<u8>, MapError> {
+        self.backend().get_block_data(pos)
+    }
+
+    /// Stores serialized blocks, replacing any already at their positions. Either all of
+    /// them are written or none are.
+    ///
+    /// The game keeps loaded blocks in memory and writes them back later, so the world
+    /// must not be open in a running server.
+    pub fn set_blocks_data(&self, blocks: &[(IVec3, Vec<u8>)]) -> Result<(), MapError> {
+        self.backend().set_blocks_data(blocks)?;
+
+        self.with_cache(|cache| {
+            for (pos, _) in blocks {
+                cache.pop(pos);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Loads every block within `area`. Positions without any stored data are skipped.
+    pub fn blocks_in(
+        &self,
+        area: Area,
+    ) -> impl Iterator<Item = (IVec3, Result<Arc<Block>, MapError>)> {
+        area.iter().filter_map(|pos| match self.get_block(pos) {
+            Err(MapError::BlockNotFound) => None,
+            result => Some((pos, result)),
+        })
+    }
+
+    /// Returns the world positions of nodes named `name` within `area`, in the order blocks
+    /// are visited. Stops after `limit` positions if one is given.
+    pub fn find_nodes(
+        &self,
+        area: Area,
+        name: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<IVec3>, MapError> {
+        let mut positions = Vec::new();
+
+        for (block_pos, block) in self.blocks_in(area) {
+            let origin = block_pos * 16;
+
+            for pos in block?.find_nodes(name) {
+                if limit.is_some_and(|limit| positions.len() >= limit) {
+                    return Ok(positions);
+                }
+
+                positions.push(origin + pos);
+            }
+        }
+
+        Ok(positions)
+    }
+
+    /// Lists the positions of every stored block, in no particular order.
+    pub fn block_positions(&self) -> Result<Vec<IVec3>, MapError> {
+        self.backend().block_positions()
+    }
+
+    /// Lists the positions of the stored blocks within `area`, in no particular order.
+    pub fn block_positions_in(&self, area: Area) -> Result<Vec<IVec3>, MapError> {
+        self.backend().block_positions_in(area)
+    }
+
+    pub fn summary(&self) -> Result<MapSummary, MapError> {
+        let mut backend = self.backend();
+
+        Ok(MapSummary {
+            block_count: backend.block_count()?,
+            bounds: backend.bounds()?,
+        })
+    }
+
+    /// Runs `f` on the cache, if there is one.
+    fn with_cache<T>(&self, f: impl FnOnce(&mut LruCache<IVec3, Arc<Block>>) -> T) -> Option<T> {
+        let cache = self.cache.as_ref()?;
+        // like the backend, a panic elsewhere doesn't leave the cache in a broken state
+        let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        Some(f(&mut cache))
+    }
+
+    fn backend(&self) -> MutexGuard<'_, Box<dyn MapBackend>> {
+        // A panic while the lock was held (e.g. in a worker thread) only interrupted a single
+        // read, so the backend itself is still usable.
+        self.backend.lock().unwrap_or_else(PoisonError::into_inner)
     }
 }
 
-pub trait MapBackend: 'static {
+pub trait MapBackend: Send + 'static {
     fn get_block_data(&mut self, pos: IVec3) -> Result<Vec<u8>, MapError>;
+
+    /// Returns the data of each block in `positions` that is stored, in any order. Backends
+    /// should override this when they can fetch several blocks in one go.
+    fn get_blocks_data(&mut self, positions: &[IVec3]) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        let mut blocks = Vec::new();
+
+        for &pos in positions {
+            match self.get_block_data(pos) {
+                Ok(data) => blocks.push((pos, data)),
+                Err(MapError::BlockNotFound) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Tells whether a block is stored at `pos`. Backends should override this when they can
+    /// answer without loading the block's data.
+    fn has_block(&mut self, pos: IVec3) -> Result<bool, MapError> {
+        match self.get_block_data(pos) {
+            Ok(_) => Ok(true),
+            Err(MapError::BlockNotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the smallest area containing every stored block, or `None` if there are none.
+    fn bounds(&mut self) -> Result<Option<Area>, MapError>;
+
+    fn block_positions(&mut self) -> Result<Vec<IVec3>, MapError>;
+
+    /// Lists the positions of the stored blocks within `area`. Backends should override
+    /// this when they can avoid listing every block, e.g. for a thin slice of the map.
+    fn block_positions_in(&mut self, area: Area) -> Result<Vec<IVec3>, MapError> {
+        let mut positions = self.block_positions()?;
+        positions.retain(|&pos| area.contains(pos));
+
+        Ok(positions)
+    }
+
+    /// Stores each block's data in a single transaction, replacing existing blocks.
+    fn set_blocks_data(&mut self, blocks: &[(IVec3, Vec<u8>)]) -> Result<(), MapError>;
+
+    fn block_count(&mut self) -> Result<u64, MapError>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MapSummary {
+    pub block_count: u64,
+    pub bounds: Option<Area>,
+}
+
+impl fmt::Display for MapSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} blocks", self.block_count)?;
+
+        if let Some(Area { min, max }) = self.bounds {
+            write!(
+                f,
+                ", extent ({},{},{})–({},{},{})",
+                min.x, min.y, min.z, max.x, max.y, max.z
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
+#[derive(Clone)]
 pub struct Block {
+    version: u8,
+    flags: u8,
+    lighting_complete: u16,
+    timestamp: u32,
     node_data: Vec<u8>,
-    mappings: HashMap<u16, String>,
+    /// Node names by id, interned since most blocks share them.
+    mappings: HashMap<u16, Arc<str>>,
+    /// Decoded from `rest` for reading; changes to it are not written back.
+    metadata: HashMap<u16, NodeMetadata>,
+    /// Why `metadata` is empty even though `rest` holds some.
+    metadata_error: Option<String>,
+    /// Node metadata, static objects and node timers, which are kept as they were read.
+    rest: Vec<u8>,
+    /// Size of the compressed parts of the data the block was parsed from.
+    compressed_len: usize,
+    /// Size of those parts once decompressed.
+    uncompressed_len: usize,
+}
+
+// The sizes describe how the block was stored rather than what it holds, so they are left
+// out: a block written back and parsed again compares equal even if it compressed
+// differently.
+impl PartialEq for Block {
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.flags == other.flags
+            && self.lighting_complete == other.lighting_complete
+            && self.timestamp == other.timestamp
+            && self.node_data == other.node_data
+            && self.mappings == other.mappings
+            && self.metadata == other.metadata
+            && self.metadata_error == other.metadata_error
+            && self.rest == other.rest
+    }
+}
+
+/// A block position with the block parsed from its data, as returned by [`Map::get_blocks`].
+pub type LoadedBlock = (IVec3, Result<Arc<Block>, ParseError>);
+
+/// How [`Block::serialize`] sets the block's timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamp {
+    /// Keep the timestamp the block was read with.
+    Preserve,
+    /// Mark the block as last modified at this game time, in seconds. The world's current
+    /// game time is `game_time` in its `env_meta.txt`.
+    Set(u32),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Node {
     pub id: u16,
     pub param1: u8,
     pub param2: u8,
 }
 
+impl Node {
+    /// Decodes param2 the way the node's definition says it is used.
+    pub fn interpret_param2(&self, def: &NodeDef) -> Param2 {
+        Param2::decode(self.param2, def.paramtype2)
+    }
+}
+
 impl Block {
     const VOLUME: usize = 16 * 16 * 16;
 
-    pub fn parse_data(data: &[u8]) -> Result<Self, MapError> {
-        let mut cur = Cursor::new(data);
-        let version = read_u8(&mut cur)?;
+    /// Largest zstd window the decoder accepts, as a power of two. zstd refuses frames with
+    /// windows above 2^27 unless told otherwise, but the format allows up to 2^31, or 2^30
+    /// on 32-bit targets.
+    const ZSTD_WINDOW_LOG_MAX: u32 = if cfg!(target_pointer_width = "64") {
+        31
+    } else {
+        30
+    };
 
-        if version < 29 {
-            return Err(MapError::UnsupportedVersion(version));
+    /// Creates a block filled with air, with no metadata, objects or timers.
+    ///
+    /// The block is marked as generated and fully lit, with an undefined timestamp, so the
+    /// game leaves it as it is when loading it.
+    pub fn new() -> Self {
+        Self {
+            version: 29,
+            flags: 0, // generated, since the not-generated bit 0x08 is clear
+            lighting_complete: 0xffff,
+            timestamp: u32::MAX,
+            node_data: vec![0; Self::VOLUME * 4],
+            mappings: HashMap::from([(0, intern("air"))]),
+            metadata: HashMap::new(),
+            metadata_error: None,
+            // no metadata; static objects version 0 with none stored; node timers of
+            // 10 bytes each, none stored
+            rest: vec![0, 0, 0, 0, 10, 0, 0],
+            compressed_len: 0,
+            uncompressed_len: 0,
         }
+    }
 
-        let mut decoder = zstd::Decoder::new(&mut cur)?;
+    /// Parses a block as stored in the map database, using a parser kept for the current
+    /// thread.
+    pub fn parse_data(data: &[u8]) -> Result<Self, ParseError> {
+        thread_local! {
+            static PARSER: RefCell<BlockParser> = RefCell::new(BlockParser::new());
+        }
 
-        let mut buf = Vec::new();
-        decoder.read_to_end(&mut buf)?;
+        PARSER.with_borrow_mut(|parser| parser.parse(data))
+    }
+
+    /// Parses the decompressed part of a block, which follows the version byte.
+    fn parse_payload(version: u8, payload: &[u8]) -> Result<Self, ParseError> {
+        let mut cur = Cursor::new(payload);
+        let flags = read_u8(&mut cur)?;
+        let lighting_complete = read_u16(&mut cur)?;
+        let timestamp = read_u32(&mut cur)?;
+        let mappings = read_mappings(&mut cur)?;
+
+        let content_width = read_u8(&mut cur)?;
+        let params_width = read_u8(&mut cur)?;
+        let mut remaining = &payload[cur.position() as usize..];
+        let node_data = Self::read_node_data(&mut remaining, content_width, params_width)?;
+        cur.set_position((payload.len() - remaining.len()) as u64);
+
+        let mut rest = Vec::new();
+        cur.read_to_end(&mut rest)?;
+        let (metadata, metadata_error) = read_metadata_leniently(&rest);
+
+        Ok(Self {
+            version,
+            flags,
+            lighting_complete,
+            timestamp,
+            node_data,
+            mappings,
+            metadata,
+            metadata_error,
+            rest,
+            compressed_len: 0,
+            uncompressed_len: 0,
+        })
+    }
 
-        let mut cur = Cursor::new(buf);
-        let _flags = read_u8(&mut cur)?;
-        let _lighting_complete = read_u16(&mut cur)?;
-        let _timestamp = read_u32(&mut cur)?;
-        let _mapping_version = read_u8(&mut cur)?;
+    /// Parses a block of versions 25 to 28, which follows the version byte uncompressed.
+    ///
+    /// These versions compress the node data and the metadata as two separate zlib streams,
+    /// and store the timestamp and mapping after the static objects rather than up front.
+    /// The block is converted to the layout of version 29, which is what
+    /// [`Block::serialize`] writes.
+    fn parse_legacy(version: u8, body: &[u8]) -> Result<Self, ParseError> {
+        let mut cur = Cursor::new(body);
+        let flags = read_u8(&mut cur)?;
+        let lighting_complete = if version >= 27 {
+            read_u16(&mut cur)?
+        } else {
+            // not tracked before version 27; the game treats it as complete
+            0xffff
+        };
 
-        let mappings_count = read_u16(&mut cur)?;
+        let content_width = read_u8(&mut cur)?;
+        let params_width = read_u8(&mut cur)?;
 
-        let mut mappings = HashMap::new();
+        let node_data_start = cur.position();
+        let raw_node_data = read_zlib(&mut cur)?;
+        let node_data =
+            Self::read_node_data(&mut raw_node_data.as_slice(), content_width, params_width)?;
 
-        for _ in 0..mappings_count {
-            let id = read_u16(&mut cur)?;
-            let name = read_string(&mut cur)?;
+        let mut rest = read_zlib(&mut cur)?;
+        let (metadata, metadata_error) = read_metadata_leniently(&rest);
 
-            mappings.insert(id, name);
-        }
+        // the node data and the metadata are the only compressed parts
+        let compressed_len = (cur.position() - node_data_start) as usize;
+        let uncompressed_len = raw_node_data.len() + rest.len();
 
-        let _content_width = read_u8(&mut cur);
-        let _params_width = read_u8(&mut cur);
+        let static_objects_start = cur.position() as usize;
+        skip_static_objects(&mut cur)?;
+        rest.extend_from_slice(&body[static_objects_start..cur.position() as usize]);
 
-        let mut node_data = vec![0; Self::VOLUME * 4];
-        cur.read_exact(&mut node_data)?;
+        let timestamp = read_u32(&mut cur)?;
+        let mappings = read_mappings(&mut cur)?;
+
+        // node timers, the same as in version 29
+        cur.read_to_end(&mut rest)?;
 
         Ok(Self {
+            version: 29,
+            flags,
+            lighting_complete,
+            timestamp,
             node_data,
             mappings,
+            metadata,
+            metadata_error,
+            rest,
+            compressed_len,
+            uncompressed_len,
         })
     }
 
+    /// Reads the ids, param1 and param2 of every node, stored as arrays one after another.
+    ///
+    /// Ids take `content_width` bytes each. Single-byte ids are widened to two bytes, so
+    /// the block always holds them the way [`Block::serialize`] writes them. Advances
+    /// `data` past the node data.
+    ///
+    /// Checking the length here, rather than relying on the reads, guarantees that
+    /// [`Block::get_node`] can index anywhere in the result.
+    fn read_node_data(
+        data: &mut &[u8],
+        content_width: u8,
+        params_width: u8,
+    ) -> Result<Vec<u8>, ParseError> {
+        if !matches!(content_width, 1 | 2) || params_width != 2 {
+            return Err(ParseError::UnexpectedFormat(format!(
+                "content width {content_width} and params width {params_width}"
+            )));
+        }
+
+        let ids_len = Self::VOLUME * usize::from(content_width);
+        let expected_len = ids_len + Self::VOLUME * 2;
+        if data.len() < expected_len {
+            return Err(ParseError::UnexpectedFormat(format!(
+                "{} bytes of node data, expected {expected_len}",
+                data.len()
+            )));
+        }
+
+        let (ids, params) = data[..expected_len].split_at(ids_len);
+        let mut node_data = Vec::with_capacity(Self::VOLUME * 4);
+
+        if content_width == 1 {
+            node_data.extend(ids.iter().flat_map(|&id| [0, id]));
+        } else {
+            node_data.extend_from_slice(ids);
+        }
+        node_data.extend_from_slice(params);
+
+        *data = &data[expected_len..];
+
+        Ok(node_data)
+    }
+
+    /// Encodes the block in the format [`Block::parse_data`] reads, ready to be stored in
+    /// the map.
+    ///
+    /// The flags (underground, day/night differs, generated) and the lighting state are
+    /// written back unchanged, since the game relies on them to decide whether the block
+    /// needs to be lit or generated again.
+    pub fn serialize(&self, timestamp: Timestamp) -> Result<Vec<u8>, std::io::Error> {
+        self.serialize_with_level(timestamp, 0)
+    }
+
+    /// Like [`Block::serialize`], compressing at the given zstd level (1-22, or 0 for zstd's
+    /// default).
+    pub fn serialize_with_level(
+        &self,
+        timestamp: Timestamp,
+        level: i32,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        let timestamp = match timestamp {
+            Timestamp::Preserve => self.timestamp,
+            Timestamp::Set(timestamp) => timestamp,
+        };
+
+        let mut buf = Vec::new();
+        buf.write_all(&[self.flags])?;
+        buf.write_all(&self.lighting_complete.to_be_bytes())?;
+        buf.write_all(&timestamp.to_be_bytes())?;
+
+        let mut mappings: Vec<_> = self.mappings.iter().collect();
+        mappings.sort_by_key(|&(&id, _)| id);
+
+        buf.write_all(&[0])?; // mapping version
+        buf.write_all(&(mappings.len() as u16).to_be_bytes())?;
+        for (id, name) in mappings {
+            buf.write_all(&id.to_be_bytes())?;
+            buf.write_all(&(name.len() as u16).to_be_bytes())?;
+            buf.write_all(name.as_bytes())?;
+        }
+
+        buf.write_all(&[2, 2])?; // content width, params width
+        buf.write_all(&self.node_data)?;
+        buf.write_all(&self.rest)?;
+
+        let mut data = vec![self.version];
+        // compressing in one go tells zstd the size up front, so high levels can use
+        // tables sized for a block rather than their full window
+        data.extend(zstd::bulk::compress(&buf, level)?);
+
+        Ok(data)
+    }
+
+    /// Bit 0x01 marks the block as underground, 0x02 as looking different at day and at
+    /// night, and 0x08 as not generated yet.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Which faces of the block the game has finished lighting, one bit per direction for
+    /// day and for night light. 0xffff means lighting is complete.
+    pub fn lighting_complete(&self) -> u16 {
+        self.lighting_complete
+    }
+
+    /// Game time at which the block was last modified, in seconds, or 0xffffffff if
+    /// undefined.
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Like [`Block::timestamp`], with `None` for an undefined timestamp.
+    pub fn timestamp_opt(&self) -> Option<u32> {
+        (self.timestamp != u32::MAX).then_some(self.timestamp)
+    }
+
+    /// Size of the compressed parts of the data the block was parsed from: the zstd frame
+    /// for version 29 and later, the node data and metadata zlib streams before that. 0 for
+    /// blocks that were not parsed.
+    pub fn compressed_len(&self) -> usize {
+        self.compressed_len
+    }
+
+    /// Size of the parts counted by [`Block::compressed_len`] once decompressed.
+    pub fn uncompressed_len(&self) -> usize {
+        self.uncompressed_len
+    }
+
     pub fn get_name_by_id(&self, id: u16) -> Option<&str> {
-        self.mappings.get(&id).map(|s| s.as_str())
+        self.mappings.get(&id).map(|name| name.as_ref())
+    }
+
+    /// Like [`Block::get_name_by_id`], returning the shared copy of the name so it can be
+    /// kept around without copying it.
+    pub fn get_interned_name(&self, id: u16) -> Option<Arc<str>> {
+        self.mappings.get(&id).cloned()
+    }
+
+    /// Returns the node ids used in this block that have no name in its mapping, in
+    /// ascending order. The game never writes such blocks, so they point to corruption.
+    pub fn unmapped_ids(&self) -> Vec<u16> {
+        let mut ids: Vec<_> = (0..Self::VOLUME)
+            .map(|index| self.node_id(index))
+            .filter(|id| !self.mappings.contains_key(id))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        ids
+    }
+
+    /// Copies the block as it is now, to return to later, e.g. when undoing an edit.
+    ///
+    /// This is a full copy of about 16 KiB of node data, so editors should keep one per
+    /// block touched by an edit rather than one per changed node.
+    pub fn snapshot(&self) -> Block {
+        self.clone()
+    }
+
+    /// Counts how many nodes of each type this block contains.
+    pub fn node_counts(&self) -> HashMap<&str, u32> {
+        let mut counts = HashMap::new();
+
+        for index in 0..Self::VOLUME {
+            if let Some(name) = self.get_name_by_id(self.node_id(index)) {
+                *counts.entry(name).or_insert(0) += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Returns the positions within this block of nodes named `name`.
+    pub fn find_nodes(&self, name: &str) -> impl Iterator<Item = IVec3> + '_ {
+        let id = self
+            .mappings
+            .iter()
+            .find_map(|(&id, mapped)| (mapped.as_ref() == name).then_some(id));
+
+        (0..Self::VOLUME)
+            .filter(move |&index| id == Some(self.node_id(index)))
+            .map(|index| {
+                let index = index as i32;
+                IVec3::new(index % 16, index / 16 % 16, index / (16 * 16))
+            })
     }
 
+    /// Returns the node at `pos`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` is outside the block; see [`Block::try_get_node`].
     pub fn get_node(&self, pos: IVec3) -> Node {
-        let node_index = Self::node_index(pos);
+        self.try_get_node(pos)
+            .unwrap_or_else(|| panic!("node position {pos} is outside the block"))
+    }
+
+    /// Returns the node at `pos`, or `None` if `pos` is outside the block.
+    pub fn try_get_node(&self, pos: IVec3) -> Option<Node> {
+        let node_index = Self::node_index(pos)?;
 
-        let id_hi = self.node_data[2 * node_index] as u16;
-        let id_lo = self.node_data[2 * node_index + 1] as u16;
         let param1 = self.node_data[Self::VOLUME * 2 + node_index];
         let param2 = self.node_data[Self::VOLUME * 3 + node_index];
 
-        Node {
-            id: (id_hi << 8) | id_lo,
+        Some(Node {
+            id: self.node_id(node_index),
             param1,
             param2,
+        })
+    }
+
+    pub fn set_node(&mut self, pos: IVec3, node: Node) {
+        let node_index = Self::node_index(pos)
+            .unwrap_or_else(|| panic!("node position {pos} is outside the block"));
+
+        self.node_data[2 * node_index..2 * node_index + 2].copy_from_slice(&node.id.to_be_bytes());
+        self.node_data[Self::VOLUME * 2 + node_index] = node.param1;
+        self.node_data[Self::VOLUME * 3 + node_index] = node.param2;
+    }
+
+    /// Returns the metadata stored for the node at `pos`, if any. A node can keep metadata
+    /// after it was replaced, so this doesn't depend on what the node is.
+    pub fn metadata(&self, pos: IVec3) -> Option<&NodeMetadata> {
+        let node_index = Self::node_index(pos)?;
+        self.metadata.get(&(node_index as u16))
+    }
+
+    /// Why the block's metadata couldn't be decoded, in which case [`Block::metadata`]
+    /// finds none. The metadata is still written back as it was read.
+    pub fn metadata_error(&self) -> Option<&str> {
+        self.metadata_error.as_deref()
+    }
+
+    /// Returns the id `name` has in this block, adding it to the mapping if the block has
+    /// no such node yet.
+    pub fn get_or_insert_id(&mut self, name: &str) -> u16 {
+        if let Some((&id, _)) = self
+            .mappings
+            .iter()
+            .find(|(_, mapped)| mapped.as_ref() == name)
+        {
+            return id;
         }
+
+        let id = (0..=u16::MAX)
+            .find(|id| !self.mappings.contains_key(id))
+            .expect("a block has at most 4096 different nodes");
+        self.mappings.insert(id, intern(name));
+
+        id
     }
 
-    fn node_index(pos: IVec3) -> usize {
-        assert!(pos.x >= 0 && pos.x < 16);
-        assert!(pos.y >= 0 && pos.y < 16);
-        assert!(pos.z >= 0 && pos.z < 16);
+    fn node_id(&self, node_index: usize) -> u16 {
+        let id_hi = self.node_data[2 * node_index] as u16;
+        let id_lo = self.node_data[2 * node_index + 1] as u16;
+
+        (id_hi << 8) | id_lo
+    }
+
+    fn node_index(pos: IVec3) -> Option<usize> {
+        let in_bounds = pos.cmpge(IVec3::ZERO).all() && pos.cmplt(IVec3::splat(16)).all();
+        if !in_bounds {
+            return None;
+        }
 
-        pos.z as usize * 16 * 16 + pos.y as usize * 16 + pos.x as usize
+        // all components are in 0..16, so this stays below VOLUME
+        Some(pos.z as usize * 16 * 16 + pos.y as usize * 16 + pos.x as usize)
     }
 }
 
-fn read_u8(r: &mut impl Read) -> Result<u8, std::io::Error> {
+/// Parses blocks one after another, reusing a single zstd context and output buffer.
+///
+/// Setting up a new decoder takes a noticeable share of the time spent on a small block,
+/// which adds up when loading thousands of them. [`Block::parse_data`] keeps one of these
+/// per thread; code parsing many blocks in a loop can also hold its own.
+pub struct BlockParser {
+    decoder: Decoder<'static>,
+    buffer: Vec<u8>,
+}
+
+impl BlockParser {
+    /// Room to reserve for the decompressed data at first. Node data alone is 16 KiB.
+    const INITIAL_CAPACITY: usize = 32 * 1024;
+
+    pub fn new() -> Self {
+        let mut decoder = Decoder::new().expect("failed to create a zstd decoder");
+        decoder
+            .set_parameter(DParameter::WindowLogMax(Block::ZSTD_WINDOW_LOG_MAX))
+            .expect("window size is within zstd's limits");
+
+        Self {
+            decoder,
+            buffer: Vec::with_capacity(Self::INITIAL_CAPACITY),
+        }
+    }
+
+    pub fn parse(&mut self, data: &[u8]) -> Result<Block, ParseError> {
+        let mut cur = Cursor::new(data);
+        let version = read_u8(&mut cur)?;
+        let body = &data[1..];
+
+        match version {
+            25..=28 => Block::parse_legacy(version, body),
+            29.. => {
+                let compressed_len = self.decompress(body).map_err(ParseError::Decompression)?;
+                let mut block = Block::parse_payload(version, &self.buffer)?;
+                block.compressed_len = compressed_len;
+                block.uncompressed_len = self.buffer.len();
+
+                Ok(block)
+            }
+            _ => Err(ParseError::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Decompresses a single zstd frame into `self.buffer`, returning the size of the frame.
+    fn decompress(&mut self, compressed: &[u8]) -> std::io::Result<usize> {
+        // also recovers from a frame that failed to decompress last time
+        self.decoder.reinit()?;
+        self.buffer.clear();
+
+        let mut input = InBuffer::around(compressed);
+
+        loop {
+            if self.buffer.len() == self.buffer.capacity() {
+                self.buffer.reserve(self.buffer.capacity());
+            }
+
+            let pos = self.buffer.len();
+            let mut output = OutBuffer::around_pos(&mut self.buffer, pos);
+            let remaining = self.decoder.run(&mut input, &mut output)?;
+
+            if remaining == 0 {
+                return Ok(input.pos());
+            }
+
+            if input.pos() == compressed.len() && output.pos() < output.capacity() {
+                return Err(std::io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "incomplete frame",
+                ));
+            }
+        }
+    }
+}
+
+pub(crate) fn read_u8(r: &mut impl Read) -> Result<u8, std::io::Error> {
     let mut buf = [0; 1];
     r.read_exact(&mut buf)?;
     Ok(buf[0])
 }
 
-fn read_u16(r: &mut impl Read) -> Result<u16, std::io::Error> {
+pub(crate) fn read_u16(r: &mut impl Read) -> Result<u16, std::io::Error> {
     let mut buf = [0; 2];
     r.read_exact(&mut buf)?;
     Ok(u16::from_be_bytes(buf))
 }
 
-fn read_u32(r: &mut impl Read) -> Result<u32, std::io::Error> {
+pub(crate) fn read_u32(r: &mut impl Read) -> Result<u32, std::io::Error> {
     let mut buf = [0; 4];
     r.read_exact(&mut buf)?;
     Ok(u32::from_be_bytes(buf))
 }
 
-fn read_string(r: &mut impl Read) -> Result<String, MapError> {
+fn read_string(r: &mut impl Read) -> Result<String, ParseError> {
     let len = read_u16(r)?;
     let mut data = vec![0; len as usize];
     r.read_exact(&mut data)?;
     let string = String::from_utf8(data)?;
     Ok(string)
 }
+
+fn read_mappings(r: &mut impl Read) -> Result<HashMap<u16, Arc<str>>, ParseError> {
+    let _mapping_version = read_u8(r)?;
+
+    let mappings_count = read_u16(r)?;
+
+    let mut mappings = HashMap::new();
+
+    for _ in 0..mappings_count {
+        let id = read_u16(r)?;
+        let name = read_string(r)?;
+
+        mappings.insert(id, intern(&name));
+    }
+
+    Ok(mappings)
+}
+
+/// Reads one zlib stream, leaving `r` right after its end.
+fn read_zlib(r: &mut impl BufRead) -> Result<Vec<u8>, ParseError> {
+    let mut data = Vec::new();
+    ZlibDecoder::new(r)
+        .read_to_end(&mut data)
+        .map_err(ParseError::ZlibDecompression)?;
+    Ok(data)
+}
+
+fn skip_static_objects(r: &mut impl Read) -> Result<(), ParseError> {
+    let _version = read_u8(r)?;
+    let count = read_u16(r)?;
+
+    for _ in 0..count {
+        // type, then the position as three fixed-point i32s
+        let mut header = [0; 13];
+        r.read_exact(&mut header)?;
+
+        let data_len = read_u16(r)?;
+        let skipped = std::io::copy(&mut r.take(data_len.into()), &mut std::io::sink())?;
+        if skipped < data_len.into() {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes the metadata list at the start of `data`. Mods can store metadata odd enough
+/// not to decode, which shouldn't make the nodes unreadable, so the error is returned
+/// alongside no metadata instead.
+fn read_metadata_leniently(data: &[u8]) -> (HashMap<u16, NodeMetadata>, Option<String>) {
+    match read_metadata_list(&mut &data[..]) {
+        Ok(metadata) => (metadata, None),
+        Err(err) => (HashMap::new(), Some(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+
+    /// Serves an empty block at every position, counting the reads.
+    struct CountingBackend(Arc<Mutex<u32>>);
+
+    impl MapBackend for CountingBackend {
+        fn get_block_data(&mut self, _pos: IVec3) -> Result<Vec<u8>, MapError> {
+            *self.0.lock().unwrap() += 1;
+            Ok(Block::new().serialize(Timestamp::Preserve)?)
+        }
+
+        fn bounds(&mut self) -> Result<Option<Area>, MapError> {
+            Ok(None)
+        }
+
+        fn block_positions(&mut self) -> Result<Vec<IVec3>, MapError> {
+            Ok(Vec::new())
+        }
+
+        fn set_blocks_data(&mut self, _blocks: &[(IVec3, Vec<u8>)]) -> Result<(), MapError> {
+            Ok(())
+        }
+
+        fn block_count(&mut self) -> Result<u64, MapError> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn cache_keeps_recent_blocks() {
+        let reads = Arc::new(Mutex::new(0));
+        let map = Map::with_cache_capacity(CountingBackend(reads.clone()), 2);
+
+        map.get_block(ivec3(0, 0, 0)).unwrap();
+        map.get_block(ivec3(1, 0, 0)).unwrap();
+        map.get_block(ivec3(0, 0, 0)).unwrap();
+        assert_eq!(*reads.lock().unwrap(), 2);
+
+        // evicts (1, 0, 0), the least recently used
+        map.get_block(ivec3(2, 0, 0)).unwrap();
+        map.get_block(ivec3(0, 0, 0)).unwrap();
+        assert_eq!(*reads.lock().unwrap(), 3);
+        map.get_block(ivec3(1, 0, 0)).unwrap();
+        assert_eq!(*reads.lock().unwrap(), 4);
+
+        map.set_blocks_data(&[(ivec3(1, 0, 0), Vec::new())])
+            .unwrap();
+        map.get_block(ivec3(1, 0, 0)).unwrap();
+        assert_eq!(*reads.lock().unwrap(), 5);
+    }
+
+    #[test]
+    fn prefetched_blocks_are_cached() {
+        let reads = Arc::new(Mutex::new(0));
+        let map = Map::with_cache_capacity(CountingBackend(reads.clone()), 4);
+        let path = [
+            ivec3(0, 0, 0),
+            ivec3(1, 0, 0),
+            ivec3(0, 0, 0),
+            ivec3(2, 0, 0),
+        ];
+
+        map.prefetch(&path).unwrap();
+        assert_eq!(*reads.lock().unwrap(), 3);
+
+        for pos in path {
+            map.get_block(pos).unwrap();
+        }
+        map.prefetch(&path).unwrap();
+        assert_eq!(*reads.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn zero_capacity_disables_the_cache() {
+        let reads = Arc::new(Mutex::new(0));
+        let map = Map::with_cache_capacity(CountingBackend(reads.clone()), 0);
+
+        map.get_block(IVec3::ZERO).unwrap();
+        map.get_block(IVec3::ZERO).unwrap();
+        assert_eq!(*reads.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn node_index_covers_the_block() {
+        assert_eq!(Block::node_index(IVec3::ZERO), Some(0));
+        assert_eq!(Block::node_index(ivec3(1, 0, 0)), Some(1));
+        assert_eq!(Block::node_index(ivec3(0, 1, 0)), Some(16));
+        assert_eq!(Block::node_index(ivec3(0, 0, 1)), Some(256));
+        assert_eq!(Block::node_index(IVec3::splat(15)), Some(Block::VOLUME - 1));
+    }
+
+    #[test]
+    fn node_index_rejects_positions_outside_the_block() {
+        for pos in [
+            ivec3(-1, 0, 0),
+            ivec3(0, 16, 0),
+            ivec3(0, 0, 16),
+            IVec3::splat(i32::MIN),
+            IVec3::splat(i32::MAX),
+        ] {
+            assert_eq!(Block::node_index(pos), None, "indexing {pos}");
+        }
+    }
+
+    #[test]
+    fn try_get_node_reads_within_bounds_only() {
+        let mut block = Block::new();
+        let stone = Node {
+            id: block.get_or_insert_id("default:stone"),
+            param1: 1,
+            param2: 2,
+        };
+        block.set_node(IVec3::splat(15), stone);
+
+        assert_eq!(block.try_get_node(IVec3::splat(15)), Some(stone));
+        assert_eq!(block.try_get_node(ivec3(16, 15, 15)), None);
+        assert_eq!(block.try_get_node(ivec3(0, -1, 0)), None);
+    }
+
+    /// Builds a block in the layout of versions 25 to 28, with a stone node at the origin
+    /// and a single static object.
+    fn legacy_block(version: u8) -> Vec<u8> {
+        let zlib = |data: &[u8]| {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let mut node_data = vec![0; Block::VOLUME * 4];
+        node_data[1] = 1; // id of the first node, big endian
+        node_data[Block::VOLUME * 2] = 0x0f; // its param1
+
+        let mut data = vec![version, 0x08];
+        if version >= 27 {
+            data.extend(0x0102u16.to_be_bytes());
+        }
+        data.extend([2, 2]);
+        data.extend(zlib(&node_data));
+        data.extend(zlib(&[0])); // no metadata
+        data.extend([0, 0, 1, 7]); // static objects version, count, and the object's type
+        data.extend([0; 12]);
+        data.extend([0, 3]);
+        data.extend(b"abc");
+        data.extend(1234u32.to_be_bytes());
+        data.extend([0, 0, 2, 0, 0, 0, 3]);
+        data.extend(b"air");
+        data.extend([0, 1, 0, 13]);
+        data.extend(b"default:stone");
+        data.extend([10, 0, 0]); // no node timers
+
+        data
+    }
+
+    #[test]
+    fn legacy_versions_are_parsed() {
+        for version in 25..=28 {
+            let block = Block::parse_data(&legacy_block(version)).unwrap();
+
+            let node = block.get_node(IVec3::ZERO);
+            assert_eq!(block.get_name_by_id(node.id), Some("default:stone"));
+            assert_eq!(node.param1, 0x0f);
+            assert_eq!(block.get_node(ivec3(1, 0, 0)).id, 0);
+            assert_eq!(block.timestamp(), 1234);
+            assert_eq!(block.flags(), 0x08);
+            assert_eq!(
+                block.lighting_complete,
+                if version >= 27 { 0x0102 } else { 0xffff }
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_blocks_serialize_as_version_29() {
+        let block = Block::parse_data(&legacy_block(28)).unwrap();
+        let data = block.serialize(Timestamp::Preserve).unwrap();
+
+        assert_eq!(data[0], 29);
+        assert!(Block::parse_data(&data).unwrap() == block);
+    }
+
+    #[test]
+    fn truncated_legacy_block_is_an_error() {
+        let data = legacy_block(28);
+        assert!(Block::parse_data(&data[..data.len() - 20]).is_err());
+    }
+
+    /// Serializes `block` with `metadata` in place of its metadata and parses it again.
+    fn with_metadata(block: &Block, metadata: &[u8]) -> Result<Block, ParseError> {
+        let mut block = block.clone();
+        block.rest = [metadata, &[0, 0, 0, 10, 0, 0]].concat();
+        Block::parse_data(&block.serialize(Timestamp::Preserve).unwrap())
+    }
+
+    const INVENTORY: &[u8] =
+        b"List main 1\nWidth 0\nItem default:dirt 5\nEndInventoryList\nEndInventory\n";
+
+    #[test]
+    fn metadata_is_read_by_position() {
+        let mut metadata = vec![2, 0, 2];
+
+        // a sign at (1, 2, 3) with a public and a private field
+        metadata.extend((1u16 + 2 * 16 + 3 * 256).to_be_bytes());
+        metadata.extend(2u32.to_be_bytes());
+        metadata.extend([0, 4]);
+        metadata.extend(b"text");
+        metadata.extend(5u32.to_be_bytes());
+        metadata.extend(b"hello");
+        metadata.push(0);
+        metadata.extend([0, 5]);
+        metadata.extend(b"owner");
+        metadata.extend(3u32.to_be_bytes());
+        metadata.extend(b"sam");
+        metadata.push(1);
+        metadata.extend(b"EndInventory\n");
+
+        // a chest at the origin, which holds only air
+        metadata.extend([0, 0]);
+        metadata.extend(0u32.to_be_bytes());
+        metadata.extend(INVENTORY);
+
+        let block = with_metadata(&Block::new(), &metadata).unwrap();
+
+        let sign = block.metadata(ivec3(1, 2, 3)).unwrap();
+        assert_eq!(sign.get("text"), Some("hello"));
+        assert_eq!(sign.get("owner"), Some("sam"));
+        assert!(sign.private_fields.contains("owner"));
+        assert!(!sign.private_fields.contains("text"));
+        assert_eq!(sign.inventory, "EndInventory\n");
+
+        let chest = block.metadata(IVec3::ZERO).unwrap();
+        assert!(chest.fields.is_empty());
+        assert_eq!(chest.inventory.as_bytes(), INVENTORY);
+
+        assert!(block.metadata(ivec3(1, 0, 0)).is_none());
+        assert!(block.metadata(ivec3(16, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn metadata_version_1_has_no_private_flag() {
+        let mut metadata = vec![1, 0, 1, 0, 0];
+        metadata.extend(1u32.to_be_bytes());
+        metadata.extend([0, 1]);
+        metadata.extend(b"a");
+        metadata.extend(1u32.to_be_bytes());
+        metadata.extend(b"b");
+        metadata.extend(b"EndInventory\n");
+
+        let block = with_metadata(&Block::new(), &metadata).unwrap();
+        let node = block.metadata(IVec3::ZERO).unwrap();
+
+        assert_eq!(node.get("a"), Some("b"));
+        assert!(node.private_fields.is_empty());
+    }
+
+    #[test]
+    fn metadata_version_0_is_empty() {
+        let block = with_metadata(&Block::new(), &[0]).unwrap();
+        assert!(block.metadata.is_empty());
+    }
+
+    #[test]
+    fn bad_metadata_keeps_the_nodes() {
+        let mut stone = Block::new();
+        let id = stone.get_or_insert_id("default:stone");
+        stone.set_node(
+            IVec3::ONE,
+            Node {
+                id,
+                param1: 0,
+                param2: 0,
+            },
+        );
+
+        // unknown version, and an inventory that never ends
+        for metadata in [&[3, 0, 0][..], &[2, 0, 1, 0, 0, 0, 0, 0, 0]] {
+            let block = with_metadata(&stone, metadata).unwrap();
+
+            assert_eq!(block.get_node(IVec3::ONE).id, id);
+            assert!(block.metadata(IVec3::ZERO).is_none());
+            assert!(block.metadata_error().is_some());
+            assert!(block.rest.starts_with(metadata));
+        }
+    }
+
+    #[test]
+    fn single_byte_ids_are_widened() {
+        let mut payload = vec![0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]; // flags, lighting, timestamp
+        payload.extend([0, 0, 2, 0, 0, 0, 3]);
+        payload.extend(b"air");
+        payload.extend([0, 7, 0, 13]);
+        payload.extend(b"default:stone");
+        payload.extend([1, 2]); // content width, params width
+
+        let mut ids = vec![0; Block::VOLUME];
+        ids[Block::VOLUME - 1] = 7;
+        payload.extend(ids);
+        let mut params = vec![0; Block::VOLUME * 2];
+        params[Block::VOLUME - 1] = 0x0f; // param1
+        params[Block::VOLUME * 2 - 1] = 3; // param2
+        payload.extend(params);
+        payload.extend([0, 0, 0, 0, 10, 0, 0]);
+
+        let mut data = vec![29];
+        data.extend(zstd::encode_all(payload.as_slice(), 0).unwrap());
+
+        let block = Block::parse_data(&data).unwrap();
+        let node = block.get_node(IVec3::splat(15));
+        assert_eq!(block.get_name_by_id(node.id), Some("default:stone"));
+        assert_eq!((node.param1, node.param2), (0x0f, 3));
+        assert_eq!(block.get_node(IVec3::ZERO).id, 0);
+
+        let data = block.serialize(Timestamp::Preserve).unwrap();
+        assert!(Block::parse_data(&data).unwrap() == block);
+    }
+
+    #[test]
+    fn parsing_records_stored_sizes() {
+        let data = Block::new().serialize(Timestamp::Preserve).unwrap();
+        let block = Block::parse_data(&data).unwrap();
+
+        assert_eq!(block.compressed_len(), data.len() - 1);
+        assert_eq!(
+            block.uncompressed_len(),
+            zstd::decode_all(&data[1..]).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn unknown_widths_are_an_error() {
+        for widths in [[0, 2], [3, 2], [2, 1]] {
+            let mut data = Block::new().serialize(Timestamp::Preserve).unwrap();
+            let mut payload = zstd::decode_all(&data[1..]).unwrap();
+            // flags, lighting, timestamp and the mapping of air come first
+            payload[17..19].copy_from_slice(&widths);
+            data.truncate(1);
+            data.extend(zstd::encode_all(payload.as_slice(), 0).unwrap());
+
+            assert!(
+                matches!(
+                    Block::parse_data(&data),
+                    Err(ParseError::UnexpectedFormat(_))
+                ),
+                "widths {widths:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_node_data_is_an_error() {
+        let block = Block::new().serialize(Timestamp::Preserve).unwrap();
+        let mut payload = zstd::decode_all(&block[1..]).unwrap();
+        // drop everything after the node data, then its last byte
+        payload.truncate(payload.len() - Block::new().rest.len() - 1);
+
+        let mut data = vec![block[0]];
+        data.extend(zstd::encode_all(payload.as_slice(), 0).unwrap());
+
+        assert!(matches!(
+            Block::parse_data(&data),
+            Err(ParseError::UnexpectedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use glam::IVec3;
+
+use crate::{Area, MapBackend, MapError};
+
+/// Keeps block data in memory, so tests can build a [`Map`](crate::Map) from hand-crafted
+/// blocks without a database on disk.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryBackend {
+    blocks: HashMap<IVec3, Vec<u8>>,
+}
+
+impl InMemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores `data` as the block at `pos`, replacing any block already there.
+    pub fn insert(&mut self, pos: IVec3, data: Vec<u8>) {
+        self.blocks.insert(pos, data);
+    }
+}
+
+impl<const N: usize> From<[(IVec3, Vec<u8>); N]> for InMemoryBackend {
+    fn from(blocks: [(IVec3, Vec<u8>); N]) -> Self {
+        blocks.into_iter().collect()
+    }
+}
+
+impl FromIterator<(IVec3, Vec<u8>)> for InMemoryBackend {
+    fn from_iter<T: IntoIterator<Item = (IVec3, Vec<u8>)>>(iter: T) -> Self {
+        Self {
+            blocks: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl MapBackend for InMemoryBackend {
+    fn get_block_data(&mut self, pos: IVec3) -> Result<Vec<u8>, MapError> {
+        self.blocks
+            .get(&pos)
+            .cloned()
+            .ok_or(MapError::BlockNotFound)
+    }
+
+    fn has_block(&mut self, pos: IVec3) -> Result<bool, MapError> {
+        Ok(self.blocks.contains_key(&pos))
+    }
+
+    fn bounds(&mut self) -> Result<Option<Area>, MapError> {
+        let bounds = self.blocks.keys().fold(None, |bounds, &pos| {
+            Some(match bounds {
+                Some(Area { min, max }) => Area::new(min.min(pos), max.max(pos)),
+                None => Area::new(pos, pos),
+            })
+        });
+
+        Ok(bounds)
+    }
+
+    fn block_positions(&mut self) -> Result<Vec<IVec3>, MapError> {
+        Ok(self.blocks.keys().copied().collect())
+    }
+
+    fn set_blocks_data(&mut self, blocks: &[(IVec3, Vec<u8>)]) -> Result<(), MapError> {
+        self.blocks.extend(blocks.iter().cloned());
+        Ok(())
+    }
+
+    fn block_count(&mut self) -> Result<u64, MapError> {
+        Ok(self.blocks.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+    use crate::{Block, Map, Timestamp};
+
+    fn empty_block() -> Vec<u8> {
+        Block::new().serialize(Timestamp::Preserve).unwrap()
+    }
+
+    #[test]
+    fn serves_inserted_blocks() {
+        let map = Map::new(InMemoryBackend::from([
+            (ivec3(0, 0, 0), empty_block()),
+            (ivec3(2, -1, 3), empty_block()),
+        ]));
+
+        assert!(map.get_block(ivec3(0, 0, 0)).is_ok());
+        assert!(matches!(
+            map.get_block(ivec3(1, 0, 0)),
+            Err(MapError::BlockNotFound)
+        ));
+        assert_eq!(map.summary().unwrap().block_count, 2);
+
+        let area = Area::new(ivec3(-1, -1, -1), ivec3(3, 3, 3));
+        assert_eq!(map.blocks_in(area).count(), 2);
+    }
+
+    #[test]
+    fn get_blocks_keeps_repeated_positions() {
+        let mut backend = InMemoryBackend::from([(IVec3::ZERO, empty_block())]);
+        backend.insert(IVec3::X, vec![29, 0xff]);
+
+        // no cache, so every block comes from a single backend query
+        let map = Map::with_cache_capacity(backend, 0);
+        let positions = [IVec3::ZERO, IVec3::X, IVec3::Y, IVec3::ZERO, IVec3::X];
+        let blocks = map.get_blocks(&positions).unwrap();
+
+        let loaded: Vec<_> = blocks
+            .iter()
+            .map(|(pos, block)| (*pos, block.is_ok()))
+            .collect();
+        assert_eq!(
+            loaded,
+            [
+                (IVec3::ZERO, true),
+                (IVec3::X, false),
+                (IVec3::ZERO, true),
+                (IVec3::X, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_data_is_a_parse_error() {
+        let mut backend = InMemoryBackend::new();
+        backend.insert(IVec3::ZERO, vec![29, 0xff]);
+        let map = Map::new(backend);
+
+        assert!(map.get_block(IVec3::ZERO).is_err());
+    }
+}
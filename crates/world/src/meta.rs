@@ -1,7 +1,56 @@
-use std::{collections::HashMap, path::Path};
+use std::path::Path;
 
 pub struct WorldMeta {
-    values: HashMap<String, String>,
+    /// In the order they appear in the file, so that saving keeps diffs small.
+    values: Vec<(String, String)>,
+}
+
+/// The map backend named by `backend` in `world.mt`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+    LevelDb,
+    Redis,
+    /// A backend this crate doesn't know about, by the name it has in `world.mt`.
+    Other(String),
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "sqlite3" => Backend::Sqlite,
+            "postgresql" => Backend::Postgres,
+            "leveldb" => Backend::LevelDb,
+            "redis" => Backend::Redis,
+            _ => Backend::Other(name.to_owned()),
+        }
+    }
+
+    /// The name of the backend as written in `world.mt`.
+    pub fn name(&self) -> &str {
+        match self {
+            Backend::Sqlite => "sqlite3",
+            Backend::Postgres => "postgresql",
+            Backend::LevelDb => "leveldb",
+            Backend::Redis => "redis",
+            Backend::Other(name) => name,
+        }
+    }
+}
+
+/// The commonly used keys of `world.mt`, parsed. Everything else is still available through
+/// the [`WorldMeta`] they were read from.
+#[derive(Clone, PartialEq, Debug)]
+pub struct WorldSettings {
+    /// SQLite when missing, as in the engine.
+    pub backend: Backend,
+    pub gameid: Option<String>,
+    pub world_name: Option<String>,
+    /// `false` when missing or not a boolean.
+    pub creative_mode: bool,
+    /// `false` when missing or not a boolean.
+    pub enable_damage: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -16,8 +65,11 @@ pub enum MetaError {
 impl WorldMeta {
     pub fn open(path: impl AsRef<Path>) -> Result<Self, MetaError> {
         let data = std::fs::read_to_string(path)?;
+        Self::parse(&data)
+    }
 
-        let mut values = HashMap::new();
+    fn parse(data: &str) -> Result<Self, MetaError> {
+        let mut meta = Self { values: Vec::new() };
 
         for line in data.lines() {
             let line = line.trim();
@@ -29,13 +81,155 @@ impl WorldMeta {
                 .split_once("=")
                 .ok_or_else(|| MetaError::InvalidFormat(line.to_string()))?;
 
-            values.insert(key.trim().to_string(), value.trim().to_string());
+            meta.set(key.trim(), value.trim());
         }
 
-        Ok(Self { values })
+        Ok(meta)
+    }
+
+    /// Writes the settings back as `key = value` lines, in the order they were read and
+    /// then set in. Blank lines in the original file are not kept.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MetaError> {
+        std::fs::write(path, self.serialize())?;
+        Ok(())
+    }
+
+    fn serialize(&self) -> String {
+        self.values
+            .iter()
+            .map(|(key, value)| format!("{key} = {value}\n"))
+            .collect()
     }
 
     pub fn get_str(&self, key: &str) -> Option<&str> {
-        self.values.get(key).map(|s| s.as_str())
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Changes the value of `key`, or adds it after the existing keys.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.values.iter_mut().find(|(k, _)| k == key) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.values.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// Reads `true` or `false`, in any case.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        let value = self.get_str(key)?;
+
+        if value.eq_ignore_ascii_case("true") {
+            Some(true)
+        } else if value.eq_ignore_ascii_case("false") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get_str(key)?.parse().ok()
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get_str(key)?.parse().ok()
+    }
+
+    pub fn parsed(&self) -> WorldSettings {
+        WorldSettings {
+            backend: self
+                .get_str("backend")
+                .map_or(Backend::Sqlite, Backend::from_name),
+            gameid: self.get_str("gameid").map(str::to_owned),
+            world_name: self.get_str("world_name").map(str::to_owned),
+            creative_mode: self.get_bool("creative_mode").unwrap_or(false),
+            enable_damage: self.get_bool("enable_damage").unwrap_or(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD_MT: &str = "
+enable_damage = true
+creative_mode = FALSE
+server_announce = yes
+backend = sqlite3
+map_meta_id = 42
+load_mod_x = -7
+gravity_scale = 1.5
+";
+
+    #[test]
+    fn typed_getters() {
+        let meta = WorldMeta::parse(WORLD_MT).unwrap();
+
+        assert_eq!(meta.get_bool("enable_damage"), Some(true));
+        assert_eq!(meta.get_bool("creative_mode"), Some(false));
+        assert_eq!(meta.get_bool("server_announce"), None);
+        assert_eq!(meta.get_bool("missing"), None);
+
+        assert_eq!(meta.get_i64("map_meta_id"), Some(42));
+        assert_eq!(meta.get_i64("load_mod_x"), Some(-7));
+        assert_eq!(meta.get_i64("gravity_scale"), None);
+        assert_eq!(meta.get_i64("backend"), None);
+
+        assert_eq!(meta.get_f64("gravity_scale"), Some(1.5));
+        assert_eq!(meta.get_f64("map_meta_id"), Some(42.0));
+        assert_eq!(meta.get_f64("missing"), None);
+
+        assert_eq!(meta.get_str("backend"), Some("sqlite3"));
+    }
+
+    #[test]
+    fn parsed_settings() {
+        let settings = WorldMeta::parse(WORLD_MT).unwrap().parsed();
+
+        assert_eq!(settings.backend, Backend::Sqlite);
+        assert_eq!(settings.gameid, None);
+        assert!(!settings.creative_mode);
+        assert!(settings.enable_damage);
+
+        let meta = WorldMeta::parse("backend = dummy\ngameid = minetest\nworld_name = Test\n");
+        let settings = meta.unwrap().parsed();
+
+        assert_eq!(settings.backend, Backend::Other("dummy".to_owned()));
+        assert_eq!(settings.backend.name(), "dummy");
+        assert_eq!(settings.gameid.as_deref(), Some("minetest"));
+        assert_eq!(settings.world_name.as_deref(), Some("Test"));
+        assert!(!settings.enable_damage);
+
+        let settings = WorldMeta::parse("").unwrap().parsed();
+        assert_eq!(settings.backend, Backend::Sqlite);
+    }
+
+    #[test]
+    fn set_keeps_the_order() {
+        let mut meta = WorldMeta::parse("b = 1\na = 2\nc=3\na = 4\n").unwrap();
+        assert_eq!(meta.get_i64("a"), Some(4));
+
+        meta.set("b", "5");
+        meta.set("d", "6");
+
+        assert_eq!(meta.serialize(), "b = 5\na = 4\nc = 3\nd = 6\n");
+    }
+
+    #[test]
+    fn saved_file_reads_back() {
+        let path = std::env::temp_dir().join(format!("world-{}.mt", std::process::id()));
+
+        let mut meta = WorldMeta::parse(WORLD_MT).unwrap();
+        meta.set("backend", "postgresql");
+        meta.save(&path).unwrap();
+
+        let saved = WorldMeta::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved.get_str("backend"), Some("postgresql"));
+        assert_eq!(saved.serialize(), meta.serialize());
     }
 }
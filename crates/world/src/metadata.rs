@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Read};
+
+use crate::ParseError;
+use crate::map::{read_u8, read_u16, read_u32};
+
+/// The string fields and inventory a mod stored on a node, such as a sign's text or a
+/// chest's contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeMetadata {
+    pub fields: HashMap<String, String>,
+    /// Names of the fields the game never sends to clients.
+    pub private_fields: HashSet<String>,
+    /// The inventory in the game's text format, from the first `List` line up to and
+    /// including `EndInventory`. Not parsed further.
+    pub inventory: String,
+}
+
+impl NodeMetadata {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+}
+
+/// Reads the metadata of a block, keyed by the flat index of the node it belongs to.
+///
+/// A version byte of 0 stands for a block without any metadata, and is followed by nothing
+/// else. Version 2 added a private flag to each field.
+pub(crate) fn read_metadata_list(
+    r: &mut impl BufRead,
+) -> Result<HashMap<u16, NodeMetadata>, ParseError> {
+    let version = read_u8(r)?;
+    if version == 0 {
+        return Ok(HashMap::new());
+    }
+
+    if version > 2 {
+        return Err(ParseError::UnexpectedFormat(format!(
+            "node metadata version {version}"
+        )));
+    }
+
+    let count = read_u16(r)?;
+    let mut metadata = HashMap::with_capacity(count.into());
+
+    for _ in 0..count {
+        let index = read_u16(r)?;
+        let field_count = read_u32(r)?;
+
+        let mut fields = HashMap::new();
+        let mut private_fields = HashSet::new();
+
+        for _ in 0..field_count {
+            let key_len = read_u16(r)?;
+            let key = read_lossy(r, key_len.into())?;
+            let value_len = read_u32(r)?;
+            let value = read_lossy(r, value_len.into())?;
+
+            if version >= 2 && read_u8(r)? != 0 {
+                private_fields.insert(key.clone());
+            }
+
+            fields.insert(key, value);
+        }
+
+        metadata.insert(
+            index,
+            NodeMetadata {
+                fields,
+                private_fields,
+                inventory: read_inventory(r)?,
+            },
+        );
+    }
+
+    Ok(metadata)
+}
+
+/// Mods store arbitrary bytes in fields, so invalid UTF-8 is replaced rather than failing
+/// the whole block.
+fn read_lossy(r: &mut impl Read, len: u64) -> Result<String, ParseError> {
+    let mut data = Vec::new();
+    r.take(len).read_to_end(&mut data)?;
+    if (data.len() as u64) < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Reads lines up to the one that ends the inventory. Empty inventories are just that line.
+fn read_inventory(r: &mut impl BufRead) -> Result<String, ParseError> {
+    let mut inventory = Vec::new();
+
+    loop {
+        let start = inventory.len();
+        if r.read_until(b'\n', &mut inventory)? == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        if inventory[start..].trim_ascii() == b"EndInventory" {
+            return Ok(String::from_utf8_lossy(&inventory).into_owned());
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use game::ParamType2;
+use glam::IVec3;
+
+/// A node's param2, decoded according to the `paramtype2` of its definition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param2 {
+    /// param2 carries no meaning for the engine; mods may still use the raw value.
+    None(u8),
+    FlowingLiquid {
+        level: u8,
+        falling: bool,
+    },
+    Wallmounted(Wallmounted),
+    Facedir(Facedir),
+    /// Rotation around Y in quarter turns.
+    FourDir(u8),
+    Leveled(u8),
+    /// Rotation around Y in degrees.
+    Degrotate(f32),
+    /// Shape of a plantlike node and the `random_offset`, `random_offset_y` and `larger`
+    /// flags as bits 0x08, 0x10 and 0x20.
+    Meshoptions {
+        shape: u8,
+        flags: u8,
+    },
+    /// Index into the node's palette.
+    Color(u8),
+    ColorFacedir {
+        color: u8,
+        facedir: Facedir,
+    },
+    ColorFourDir {
+        color: u8,
+        rotation: u8,
+    },
+    ColorWallmounted {
+        color: u8,
+        wallmounted: Wallmounted,
+    },
+    GlasslikeLiquidLevel(u8),
+    ColorDegrotate {
+        color: u8,
+        degrees: f32,
+    },
+}
+
+/// One of the 24 orientations of a node: the direction its top faces, and a rotation
+/// around that direction in quarter turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Facedir {
+    pub axis: u8,
+    pub rotation: u8,
+}
+
+/// The side of its cell a wallmounted node is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wallmounted(pub u8);
+
+impl Param2 {
+    pub fn decode(param2: u8, paramtype2: ParamType2) -> Self {
+        match paramtype2 {
+            ParamType2::None | ParamType2::Unknown => Param2::None(param2),
+            ParamType2::Flowingliquid => Param2::FlowingLiquid {
+                level: param2 & 0x07,
+                falling: param2 & 0x08 != 0,
+            },
+            ParamType2::Wallmounted => Param2::Wallmounted(Wallmounted(param2 & 0x07)),
+            ParamType2::Facedir => Param2::Facedir(Facedir::from_param2(param2)),
+            ParamType2::FourDir => Param2::FourDir(param2 & 0x03),
+            ParamType2::Leveled => Param2::Leveled(param2 & 0x7f),
+            ParamType2::Degrotate => Param2::Degrotate((param2 % 240) as f32 * 1.5),
+            ParamType2::Meshoptions => Param2::Meshoptions {
+                shape: param2 & 0x07,
+                flags: param2 & 0x38,
+            },
+            ParamType2::Color => Param2::Color(param2),
+            ParamType2::Colorfacedir => Param2::ColorFacedir {
+                color: param2 >> 5,
+                facedir: Facedir::from_param2(param2 & 0x1f),
+            },
+            ParamType2::ColorFourDir => Param2::ColorFourDir {
+                color: param2 >> 2,
+                rotation: param2 & 0x03,
+            },
+            ParamType2::Colorwallmounted => Param2::ColorWallmounted {
+                color: param2 >> 3,
+                wallmounted: Wallmounted(param2 & 0x07),
+            },
+            ParamType2::Glasslikeliquidlevel => Param2::GlasslikeLiquidLevel(param2 & 0x3f),
+            ParamType2::Colordegrotate => Param2::ColorDegrotate {
+                color: param2 >> 5,
+                degrees: (param2 & 0x1f) as f32 * 15.0,
+            },
+        }
+    }
+}
+
+impl Facedir {
+    fn from_param2(param2: u8) -> Self {
+        // values above 23 are invalid and treated as unrotated by the engine
+        let value = if param2 & 0x1f < 24 { param2 & 0x1f } else { 0 };
+
+        Self {
+            axis: value >> 2,
+            rotation: value & 0x03,
+        }
+    }
+
+    /// The direction the node's top faces.
+    pub fn up(&self) -> IVec3 {
+        match self.axis {
+            0 => IVec3::Y,
+            1 => IVec3::Z,
+            2 => IVec3::NEG_Z,
+            3 => IVec3::X,
+            4 => IVec3::NEG_X,
+            _ => IVec3::NEG_Y,
+        }
+    }
+}
+
+impl Wallmounted {
+    /// Direction from the node to the surface it is attached to. Values 6 and 7 are the
+    /// ceiling and floor, turned by 90 degrees.
+    pub fn direction(&self) -> IVec3 {
+        match self.0 {
+            0 | 6 => IVec3::Y,
+            1 | 7 => IVec3::NEG_Y,
+            2 => IVec3::X,
+            3 => IVec3::NEG_X,
+            4 => IVec3::Z,
+            _ => IVec3::NEG_Z,
+        }
+    }
+}
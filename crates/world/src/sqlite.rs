@@ -1,18 +1,66 @@
 use std::path::Path;
 
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
 
-use crate::{MapError, MapBackend};
+use crate::{Area, MapBackend, MapError};
 
 pub struct SqliteBackend {
     conn: Connection,
+    schema: Schema,
+}
+
+/// How the `blocks` table identifies a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Schema {
+    /// `blocks(x, y, z, data)`, written by current versions of the game.
+    Coordinates,
+    /// `blocks(pos, data)`, with the position packed by [`encode_block_pos`]. Older worlds
+    /// keep this layout until they are migrated.
+    PackedPos,
 }
 
 impl SqliteBackend {
     pub fn new(path: impl AsRef<Path>) -> Result<Self, MapError> {
-        let conn = Connection::open(path)?;
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, MapError> {
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('blocks')")?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(stmt);
 
-        Ok(Self { conn })
+        let schema = if columns.iter().any(|column| column == "pos") {
+            Schema::PackedPos
+        } else {
+            Schema::Coordinates
+        };
+
+        Ok(Self { conn, schema })
+    }
+
+    /// Runs `sql` with the block position as its parameters: `x`, `y` and `z`, or the packed
+    /// `pos`. Positions that can't be packed are never stored, so they read as missing.
+    fn query_pos<T>(
+        &self,
+        pos: glam::IVec3,
+        coordinates_sql: &str,
+        packed_sql: &str,
+        f: impl FnOnce(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    ) -> Result<Option<T>, MapError> {
+        let result = match self.schema {
+            Schema::Coordinates => self
+                .conn
+                .query_one(coordinates_sql, [pos.x, pos.y, pos.z], f)
+                .optional()?,
+            Schema::PackedPos => match encode_block_pos(pos) {
+                Some(key) => self.conn.query_one(packed_sql, [key], f).optional()?,
+                None => None,
+            },
+        };
+
+        Ok(result)
     }
 }
 
@@ -25,11 +73,421 @@ impl MapBackend for SqliteBackend {
               AND y = ?
               AND z = ?
             LIMIT 1";
+        const PACKED_SQL: &str = "SELECT data FROM blocks WHERE pos = ? LIMIT 1";
+
+        let data = self.query_pos(pos, SQL, PACKED_SQL, |row| row.get(0))?;
+
+        data.ok_or(MapError::BlockNotFound)
+    }
+
+    fn get_blocks_data(
+        &mut self,
+        positions: &[glam::IVec3],
+    ) -> Result<Vec<(glam::IVec3, Vec<u8>)>, MapError> {
+        let mut blocks = Vec::with_capacity(positions.len());
+
+        // keeps the number of bound parameters under sqlite's limit of 999 in older versions
+        for batch in positions.chunks(256) {
+            match self.schema {
+                Schema::Coordinates => {
+                    // joining looks each position up by the primary key, while a row value
+                    // `IN (VALUES ...)` makes sqlite scan the whole table
+                    let sql = format!(
+                        "
+                        WITH wanted(x, y, z) AS (VALUES {})
+                        SELECT b.x, b.y, b.z, b.data
+                        FROM wanted
+                        JOIN blocks b
+                          ON b.x = wanted.x
+                         AND b.y = wanted.y
+                         AND b.z = wanted.z",
+                        vec!["(?, ?, ?)"; batch.len()].join(", ")
+                    );
+                    let params = batch.iter().flat_map(|pos| pos.to_array());
+
+                    let mut stmt = self.conn.prepare(&sql)?;
+                    let rows = stmt.query_map(params_from_iter(params), |row| {
+                        let pos = glam::IVec3::new(row.get(0)?, row.get(1)?, row.get(2)?);
+                        Ok((pos, row.get(3)?))
+                    })?;
+
+                    for row in rows {
+                        blocks.push(row?);
+                    }
+                }
+                Schema::PackedPos => {
+                    let keys: Vec<_> = batch
+                        .iter()
+                        .filter_map(|&pos| encode_block_pos(pos))
+                        .collect();
+                    if keys.is_empty() {
+                        continue;
+                    }
+
+                    let sql = format!(
+                        "SELECT pos, data FROM blocks WHERE pos IN ({})",
+                        vec!["?"; keys.len()].join(", ")
+                    );
+
+                    let mut stmt = self.conn.prepare(&sql)?;
+                    let rows = stmt.query_map(params_from_iter(keys), |row| {
+                        Ok((row.get::<_, i64>(0)?, row.get(1)?))
+                    })?;
+
+                    for row in rows {
+                        let (key, data) = row?;
+                        // the key came from encode_block_pos, so it decodes
+                        blocks.extend(decode_block_pos(key).map(|pos| (pos, data)));
+                    }
+                }
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    fn has_block(&mut self, pos: glam::IVec3) -> Result<bool, MapError> {
+        const SQL: &str = "
+            SELECT 1
+            FROM blocks
+            WHERE x = ?
+              AND y = ?
+              AND z = ?
+            LIMIT 1";
+        const PACKED_SQL: &str = "SELECT 1 FROM blocks WHERE pos = ? LIMIT 1";
+
+        let exists = self.query_pos(pos, SQL, PACKED_SQL, |_| Ok(()))?.is_some();
+
+        Ok(exists)
+    }
+
+    fn bounds(&mut self) -> Result<Option<Area>, MapError> {
+        const SQL: &str = "
+            SELECT MIN(x), MIN(y), MIN(z), MAX(x), MAX(y), MAX(z)
+            FROM blocks";
+
+        if self.schema == Schema::PackedPos {
+            // packed keys don't order by any single coordinate
+            let bounds = self
+                .block_positions()?
+                .into_iter()
+                .fold(None, |bounds, pos| {
+                    Some(match bounds {
+                        Some(Area { min, max }) => Area::new(min.min(pos), max.max(pos)),
+                        None => Area::new(pos, pos),
+                    })
+                });
+
+            return Ok(bounds);
+        }
+
+        let bounds = self.conn.query_one(SQL, [], |row| {
+            let min = try_ivec3(row.get(0)?, row.get(1)?, row.get(2)?);
+            let max = try_ivec3(row.get(3)?, row.get(4)?, row.get(5)?);
+
+            Ok(min.zip(max).map(|(min, max)| Area::new(min, max)))
+        })?;
+
+        Ok(bounds)
+    }
+
+    fn block_positions(&mut self) -> Result<Vec<glam::IVec3>, MapError> {
+        const SQL: &str = "SELECT x, y, z FROM blocks";
+        const PACKED_SQL: &str = "SELECT pos FROM blocks";
+
+        if self.schema == Schema::PackedPos {
+            let mut stmt = self.conn.prepare(PACKED_SQL)?;
+            let keys = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+
+            // keys that don't decode to a position can't be looked up either
+            return Ok(keys.into_iter().filter_map(decode_block_pos).collect());
+        }
+
+        let mut stmt = self.conn.prepare(SQL)?;
+        let positions = stmt
+            .query_map([], |row| {
+                Ok(glam::IVec3::new(row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(positions)
+    }
+
+    fn block_positions_in(&mut self, area: Area) -> Result<Vec<glam::IVec3>, MapError> {
+        const SQL: &str = "
+            SELECT x, y, z
+            FROM blocks
+            WHERE x BETWEEN ? AND ?
+              AND y BETWEEN ? AND ?
+              AND z BETWEEN ? AND ?";
+        const PACKED_SQL: &str = "SELECT pos FROM blocks WHERE pos BETWEEN ? AND ?";
+
+        if self.schema == Schema::PackedPos {
+            // Keys order by z first, so only the z range can be looked up. Each z takes a
+            // contiguous range of keys, from its lowest x and y to its highest.
+            let min_z = area.min.z.max(BLOCK_POS_MIN);
+            let max_z = area.max.z.min(BLOCK_POS_MAX);
+            if min_z > max_z {
+                return Ok(Vec::new());
+            }
+
+            let first = encode_block_pos(glam::IVec3::new(BLOCK_POS_MIN, BLOCK_POS_MIN, min_z));
+            let last = encode_block_pos(glam::IVec3::new(BLOCK_POS_MAX, BLOCK_POS_MAX, max_z));
+
+            let mut stmt = self.conn.prepare(PACKED_SQL)?;
+            let keys = stmt
+                .query_map([first, last], |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+
+            return Ok(keys
+                .into_iter()
+                .filter_map(decode_block_pos)
+                .filter(|&pos| area.contains(pos))
+                .collect());
+        }
+
+        let Area { min, max } = area;
+        let mut stmt = self.conn.prepare(SQL)?;
+        let positions = stmt
+            .query_map([min.x, max.x, min.y, max.y, min.z, max.z], |row| {
+                Ok(glam::IVec3::new(row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<_, _>>()?;
 
-        let data = self
-            .conn
-            .query_one(SQL, [&pos.x, &pos.y, &pos.z], |row| row.get(0))?;
+        Ok(positions)
+    }
+
+    fn set_blocks_data(&mut self, blocks: &[(glam::IVec3, Vec<u8>)]) -> Result<(), MapError> {
+        const SQL: &str = "
+            INSERT OR REPLACE INTO blocks (x, y, z, data)
+            VALUES (?, ?, ?, ?)";
+        const PACKED_SQL: &str = "INSERT OR REPLACE INTO blocks (pos, data) VALUES (?, ?)";
+
+        let schema = self.schema;
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(match schema {
+                Schema::Coordinates => SQL,
+                Schema::PackedPos => PACKED_SQL,
+            })?;
+
+            for (pos, data) in blocks {
+                match schema {
+                    Schema::Coordinates => stmt.execute(params![pos.x, pos.y, pos.z, data])?,
+                    Schema::PackedPos => {
+                        let key = encode_block_pos(*pos).ok_or(MapError::PosOutOfRange(*pos))?;
+                        stmt.execute(params![key, data])?
+                    }
+                };
+            }
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn block_count(&mut self) -> Result<u64, MapError> {
+        const SQL: &str = "SELECT COUNT(*) FROM blocks";
+
+        let count = self.conn.query_one(SQL, [], |row| row.get(0))?;
+
+        Ok(count)
+    }
+}
+
+fn try_ivec3(x: Option<i32>, y: Option<i32>, z: Option<i32>) -> Option<glam::IVec3> {
+    Some(glam::IVec3::new(x?, y?, z?))
+}
+
+const BLOCK_POS_MIN: i32 = -2048;
+const BLOCK_POS_MAX: i32 = 2047;
+
+/// Packs a block position into the integer key used by the legacy `blocks(pos, data)` schema.
+///
+/// Matches `getBlockAsInteger` in Luanti: `z * 0x1000000 + y * 0x1000 + x`, with each
+/// component a 12-bit signed value. Returns `None` for positions outside that range, whose
+/// keys would alias other blocks.
+pub fn encode_block_pos(pos: glam::IVec3) -> Option<i64> {
+    let in_range = pos.cmpge(glam::IVec3::splat(BLOCK_POS_MIN)).all()
+        && pos.cmple(glam::IVec3::splat(BLOCK_POS_MAX)).all();
+    if !in_range {
+        return None;
+    }
+
+    Some(pos.z as i64 * 0x1000000 + pos.y as i64 * 0x1000 + pos.x as i64)
+}
+
+/// Inverse of [`encode_block_pos`], matching `getIntegerAsBlock` in Luanti. Returns `None`
+/// for keys that no position encodes to.
+pub fn decode_block_pos(mut i: i64) -> Option<glam::IVec3> {
+    let mut next_component = || {
+        let unsigned = i.rem_euclid(4096);
+        let component = if unsigned < 2048 {
+            unsigned
+        } else {
+            unsigned - 4096
+        };
+        // same as `(i - component) / 4096`, which overflows for keys near the i64 limits
+        i = i.div_euclid(4096) + i64::from(component < 0);
+
+        component as i32
+    };
+
+    let x = next_component();
+    let y = next_component();
+    let z = next_component();
+
+    // leftover high bits would otherwise be dropped, reading some other block
+    let pos = glam::IVec3::new(x, y, z);
+    (i == 0).then_some(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+
+    // Keys as computed by Luanti's `getBlockAsInteger`.
+    const KNOWN_POSITIONS: &[(glam::IVec3, i64)] = &[
+        (ivec3(0, 0, 0), 0),
+        (ivec3(1, 2, 3), 50339841),
+        (ivec3(-1, 0, 0), -1),
+        (ivec3(0, -1, 0), -4096),
+        (ivec3(0, 0, -1), -16777216),
+        (ivec3(-1, -1, -1), -16781313),
+        (ivec3(-30, 5, -12), -201306142),
+        (ivec3(2047, 2047, 2047), 34351347711),
+        (ivec3(-2048, -2048, -2048), -34368129024),
+    ];
+
+    #[test]
+    fn encode_known_positions() {
+        for &(pos, key) in KNOWN_POSITIONS {
+            assert_eq!(encode_block_pos(pos), Some(key), "encoding {pos}");
+        }
+    }
+
+    #[test]
+    fn decode_known_positions() {
+        for &(pos, key) in KNOWN_POSITIONS {
+            assert_eq!(decode_block_pos(key), Some(pos), "decoding {key}");
+        }
+    }
+
+    fn backend(create_table: &str) -> SqliteBackend {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(create_table, []).unwrap();
+        SqliteBackend::with_connection(conn).unwrap()
+    }
+
+    const POSITIONS: &[glam::IVec3] = &[ivec3(0, 0, 0), ivec3(-30, 5, -12), ivec3(2, -1, 7)];
+
+    fn check_backend(mut backend: SqliteBackend) {
+        let blocks: Vec<_> = POSITIONS
+            .iter()
+            .enumerate()
+            .map(|(index, &pos)| (pos, vec![index as u8]))
+            .collect();
+        backend.set_blocks_data(&blocks).unwrap();
+
+        for (pos, data) in &blocks {
+            assert_eq!(
+                &backend.get_block_data(*pos).unwrap(),
+                data,
+                "reading {pos}"
+            );
+            assert!(backend.has_block(*pos).unwrap());
+        }
+
+        assert!(matches!(
+            backend.get_block_data(ivec3(1, 0, 0)),
+            Err(MapError::BlockNotFound)
+        ));
+        assert!(!backend.has_block(ivec3(0, 0, 4096)).unwrap());
+
+        let mut fetched = backend
+            .get_blocks_data(&[
+                ivec3(2, -1, 7),
+                ivec3(1, 0, 0),
+                ivec3(0, 0, 0),
+                ivec3(9999, 0, 0),
+            ])
+            .unwrap();
+        fetched.sort_by_key(|(pos, _)| pos.to_array());
+        assert_eq!(fetched, vec![blocks[0].clone(), blocks[2].clone()]);
+        assert!(backend.get_blocks_data(&[]).unwrap().is_empty());
+
+        let mut positions = backend.block_positions().unwrap();
+        positions.sort_by_key(|pos| pos.to_array());
+        let mut expected = POSITIONS.to_vec();
+        expected.sort_by_key(|pos| pos.to_array());
+        assert_eq!(positions, expected);
+
+        let area = Area::new(ivec3(-30, -1, -12), ivec3(2, 0, 7));
+        let mut positions = backend.block_positions_in(area).unwrap();
+        positions.sort_by_key(|pos| pos.to_array());
+        assert_eq!(positions, vec![ivec3(0, 0, 0), ivec3(2, -1, 7)]);
+
+        let bounds = backend.bounds().unwrap().unwrap();
+        assert_eq!(
+            (bounds.min, bounds.max),
+            (ivec3(-30, -1, -12), ivec3(2, 5, 7))
+        );
+        assert_eq!(backend.block_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn coordinate_schema() {
+        let backend = backend(
+            "CREATE TABLE blocks (x INTEGER, y INTEGER, z INTEGER, data BLOB NOT NULL, \
+             PRIMARY KEY (x, z, y))",
+        );
+        assert_eq!(backend.schema, Schema::Coordinates);
+        check_backend(backend);
+    }
+
+    #[test]
+    fn packed_pos_schema() {
+        let backend = backend("CREATE TABLE blocks (pos INT PRIMARY KEY, data BLOB)");
+        assert_eq!(backend.schema, Schema::PackedPos);
+        check_backend(backend);
+    }
+
+    #[test]
+    fn packed_pos_schema_rejects_unpackable_positions() {
+        let mut backend = backend("CREATE TABLE blocks (pos INT PRIMARY KEY, data BLOB)");
+        assert!(matches!(
+            backend.set_blocks_data(&[(ivec3(4096, 0, 0), vec![0])]),
+            Err(MapError::PosOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn empty_map_has_no_bounds() {
+        let mut backend = backend("CREATE TABLE blocks (pos INT PRIMARY KEY, data BLOB)");
+        assert!(backend.bounds().unwrap().is_none());
+        assert!(backend.block_positions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn encode_rejects_out_of_range_positions() {
+        for pos in [
+            ivec3(2048, 0, 0),
+            ivec3(0, -2049, 0),
+            ivec3(0, 0, i32::MAX),
+            ivec3(i32::MIN, i32::MIN, i32::MIN),
+        ] {
+            assert_eq!(encode_block_pos(pos), None, "encoding {pos}");
+        }
+    }
 
-        Ok(data)
+    #[test]
+    fn decode_rejects_malformed_keys() {
+        for key in [34351347712, -34368129025, i64::MAX, i64::MIN] {
+            assert_eq!(decode_block_pos(key), None, "decoding {key}");
+        }
     }
 }
@@ -4,14 +4,12 @@ pub mod world_manager;
 use std::sync::{Arc, Mutex};
 
 use egui::ThemePreference;
-use egui_wgpu::WgpuConfiguration;
 use render::VoxelRenderer;
 
 use crate::ui::View;
 use crate::world_manager::WorldManager;
 
 struct App {
-    world_manager: Arc<Mutex<WorldManager>>,
     ui: View,
 }
 
@@ -34,15 +32,12 @@ impl App {
         let controller = ui::Controller::new(Arc::clone(&world_manager));
         let view = ui::View::new(controller);
 
-        Self {
-            world_manager,
-            ui: view,
-        }
+        Self { ui: view }
     }
 }
 
 impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.ui.ui(ctx);
     }
 }
@@ -2,10 +2,9 @@ use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 
 use anyhow::{Result, anyhow};
-use egui::epaint::CircleShape;
 use egui::{
-    Align2, Area, Color32, Context, Frame, Id, Key, LayerId, Margin, Modifiers, Popup, PopupKind,
-    RichText, ScrollArea, Sense, Shape, TextEdit, TextStyle, UiBuilder, Vec2,
+    Align2, Color32, Context, Frame, Key, Margin, Modifiers, RichText, Sense, TextEdit, TextStyle,
+    UiBuilder, Vec2,
 };
 use egui_tiles::{Behavior, Container, ContainerKind, SimplificationOptions, Tile, Tree};
 use render::VoxelRenderer;
@@ -41,10 +40,10 @@ impl View {
         egui::TopBottomPanel::top("top panel").show(ctx, |ui| {
             egui::MenuBar::new().ui(ui, |ui| {
                 ui.menu_button("File", |ui| {
-                    if ui.button("Open world...").clicked() {
-                        if let Ok(world_id) = self.controller.open_world() {
-                            self.insert_pane(Pane::World(world_id));
-                        }
+                    if ui.button("Open world...").clicked()
+                        && let Ok(world_id) = self.controller.open_world()
+                    {
+                        self.insert_pane(Pane::World(world_id));
                     }
                 });
             });
@@ -131,7 +130,7 @@ impl Behavior<Pane> for TreeController {
     fn pane_ui(
         &mut self,
         ui: &mut egui::Ui,
-        tile_id: egui_tiles::TileId,
+        _tile_id: egui_tiles::TileId,
         pane: &mut Pane,
     ) -> egui_tiles::UiResponse {
         match pane {
@@ -227,7 +226,7 @@ impl WorldViewCallback {
 impl egui_wgpu::CallbackTrait for WorldViewCallback {
     fn paint(
         &self,
-        info: egui::PaintCallbackInfo,
+        _info: egui::PaintCallbackInfo,
         render_pass: &mut eframe::wgpu::RenderPass<'static>,
         callback_resources: &egui_wgpu::CallbackResources,
     ) {
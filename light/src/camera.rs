@@ -1,4 +1,4 @@
-use glam::{Mat4, Quat, Vec3, vec3};
+use glam::{Mat4, Quat, Vec2, Vec3, vec3};
 
 #[derive(Debug, Clone)]
 pub struct Camera {
@@ -6,15 +6,50 @@ pub struct Camera {
     pub pitch: f32,
     pub yaw: f32,
     pub fov: f32,
+    /// Nodes moved per frame.
+    speed: f32,
+    /// Degrees turned per unit of horizontal mouse motion.
+    sensitivity_x: f32,
+    /// Degrees turned per unit of vertical mouse motion.
+    sensitivity_y: f32,
+    /// Whether moving the mouse up looks down.
+    invert_y: bool,
+    orbit: Option<Orbit>,
+}
+
+/// Where a camera is and which way it faces, without projection settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub rotation: Quat,
+}
+
+/// Keeps the camera on a sphere around `pivot`, looking at it.
+#[derive(Debug, Clone)]
+pub struct Orbit {
+    pub pivot: Vec3,
+    pub distance: f32,
 }
 
 impl Camera {
+    const MIN_ORBIT_DISTANCE: f32 = 1.0;
+    /// Pitch in degrees the camera stops at when looking up or down, short of 90 so that
+    /// the view never flips over.
+    const MAX_PITCH: f32 = 89.0;
+    const MIN_SPEED: f32 = 0.01;
+    const MAX_SPEED: f32 = 10.0;
+
     pub fn new() -> Self {
         Camera {
             position: vec3(0.0, 0.0, 3.0),
             pitch: 0.0,
             yaw: 0.0,
             fov: 75.0,
+            speed: 0.1,
+            sensitivity_x: 0.1,
+            sensitivity_y: 0.1,
+            invert_y: false,
+            orbit: None,
         }
     }
 
@@ -30,17 +65,115 @@ impl Camera {
         self
     }
 
-    pub fn rotate(&mut self, delta_pitch: f32, delta_yaw: f32) {
-        self.pitch -= delta_pitch;
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets how many nodes the camera moves per frame, within a sensible range.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(Self::MIN_SPEED, Self::MAX_SPEED);
+    }
+
+    pub fn sensitivity(&self) -> (f32, f32) {
+        (self.sensitivity_x, self.sensitivity_y)
+    }
+
+    /// Sets the degrees turned per unit of mouse motion, horizontally and vertically.
+    pub fn set_sensitivity(&mut self, x: f32, y: f32) {
+        self.sensitivity_x = x;
+        self.sensitivity_y = y;
+    }
 
-        let pitch_modulo = ((self.pitch % 360.0) + 360.0) % 360.0;
-        let delta_yaw = if pitch_modulo > 90.0 && pitch_modulo < 270.0 {
-            -delta_yaw
+    pub fn invert_y(&self) -> bool {
+        self.invert_y
+    }
+
+    pub fn set_invert_y(&mut self, invert_y: bool) {
+        self.invert_y = invert_y;
+    }
+
+    /// Turns the camera by raw mouse motion, scaled by the sensitivity.
+    pub fn turn(&mut self, mouse_delta: Vec2) {
+        let delta_pitch = mouse_delta.y * self.sensitivity_y;
+        let delta_pitch = if self.invert_y {
+            -delta_pitch
         } else {
-            delta_yaw
+            delta_pitch
         };
 
+        self.rotate(delta_pitch, mouse_delta.x * self.sensitivity_x);
+    }
+
+    pub fn rotate(&mut self, delta_pitch: f32, delta_yaw: f32) {
+        self.pitch = (self.pitch - delta_pitch).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
         self.yaw += delta_yaw;
+
+        self.update_orbit_position();
+    }
+
+    /// Starts orbiting `pivot` at `distance`, keeping the direction the pivot is seen from.
+    pub fn set_orbit(&mut self, pivot: Vec3, distance: f32) {
+        self.look_at(pivot);
+        self.orbit = Some(Orbit {
+            pivot,
+            distance: distance.max(Self::MIN_ORBIT_DISTANCE),
+        });
+
+        self.update_orbit_position();
+    }
+
+    /// Goes back to free flight from wherever the orbit left the camera.
+    pub fn clear_orbit(&mut self) {
+        self.orbit = None;
+    }
+
+    pub fn orbit(&self) -> Option<&Orbit> {
+        self.orbit.as_ref()
+    }
+
+    /// Moves an orbiting camera `delta` nodes further away from the pivot.
+    pub fn zoom_orbit(&mut self, delta: f32) {
+        if let Some(orbit) = &mut self.orbit {
+            orbit.distance = (orbit.distance + delta).max(Self::MIN_ORBIT_DISTANCE);
+        }
+
+        self.update_orbit_position();
+    }
+
+    pub fn look_at(&mut self, target: Vec3) {
+        self.look_along(target - self.position);
+    }
+
+    pub fn look_along(&mut self, direction: Vec3) {
+        let look = direction.normalize_or_zero();
+        if look == Vec3::ZERO {
+            return;
+        }
+
+        self.pitch = look.y.clamp(-1.0, 1.0).asin().to_degrees();
+        self.yaw = look.x.atan2(-look.z).to_degrees();
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.position,
+            rotation: self.rotation(),
+        }
+    }
+
+    /// Moves the camera to `pose`. Roll is dropped, since the camera only has pitch and yaw.
+    pub fn set_pose(&mut self, pose: &CameraPose) {
+        self.position = pose.position;
+        self.look_along(pose.rotation.mul_vec3(Vec3::NEG_Z));
+
+        self.update_orbit_position();
+    }
+
+    fn update_orbit_position(&mut self) {
+        if let Some(orbit) = &self.orbit {
+            let (forward, _) = self.forward_right();
+            self.position = orbit.pivot - forward * orbit.distance;
+        }
     }
 
     fn rotation(&self) -> Quat {
@@ -69,3 +202,25 @@ impl Camera {
         projection * world_rotation * world_translation
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate_clamps_pitch() {
+        let mut camera = Camera::new();
+
+        for _ in 0..10 {
+            camera.rotate(-500.0, 0.0);
+            assert!(camera.pitch <= Camera::MAX_PITCH);
+        }
+        assert_eq!(camera.pitch, Camera::MAX_PITCH);
+
+        for _ in 0..10 {
+            camera.rotate(500.0, 0.0);
+            assert!(camera.pitch >= -Camera::MAX_PITCH);
+        }
+        assert_eq!(camera.pitch, -Camera::MAX_PITCH);
+    }
+}
@@ -0,0 +1,436 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use glam::{IVec3, UVec2, Vec3};
+use world::Area;
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub viewer: ViewerArgs,
+}
+
+#[derive(Args)]
+pub struct ViewerArgs {
+    /// World directory to open in the viewer
+    #[arg(required = true)]
+    pub world: Option<PathBuf>,
+
+    /// Show what a water surface at this world Y would flood
+    #[arg(long, allow_hyphen_values = true)]
+    pub water_level: Option<i32>,
+
+    /// Load a cube this many blocks wide around the starting block, e.g. 4 for 4x4x4
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=8))]
+    pub blocks: u32,
+
+    #[command(flatten)]
+    pub options: ViewerOptions,
+}
+
+/// Options shared by every command that opens the viewer.
+#[derive(Args)]
+pub struct ViewerOptions {
+    /// Make nodes glow when their stored light is at least this level (0-15)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=15))]
+    pub emissive_threshold: Option<u8>,
+
+    /// Game directory holding a `nodes.json` dump of its node definitions
+    #[arg(long)]
+    pub game: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub adaptive_quality: AdaptiveQualityArgs,
+
+    /// When to draw new frames
+    #[arg(long, value_enum, default_value_t)]
+    pub render_loop: RenderLoop,
+
+    /// Samples per pixel for smoothing the edges of the mesh, e.g. 4
+    #[arg(long, default_value_t = 1)]
+    pub msaa: u32,
+
+    /// How many node edits can be undone
+    #[arg(long, default_value_t = 1000)]
+    pub undo_depth: usize,
+
+    #[command(flatten)]
+    pub y_clip: YClipArgs,
+
+    #[command(flatten)]
+    pub tour: TourArgs,
+}
+
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenderLoop {
+    /// Draw frames back to back, even when nothing changes
+    Continuous,
+    /// Draw only when something changed, and sleep while the camera is still
+    #[default]
+    OnDemand,
+}
+
+#[derive(Args)]
+pub struct TourArgs {
+    /// Save the tour as numbered PNG frames in this directory when playing it, instead of
+    /// showing it
+    #[arg(long)]
+    pub tour_out: Option<PathBuf>,
+
+    /// Frames per second of the saved tour
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tour_fps: u32,
+}
+
+#[derive(Args)]
+pub struct AdaptiveQualityArgs {
+    /// Render at this fraction of the window's resolution while the camera moves, e.g. 0.5
+    #[arg(long)]
+    pub moving_scale: Option<f32>,
+
+    /// Seconds the camera has to stay still before returning to full resolution
+    #[arg(long, default_value_t = 0.25)]
+    pub settle_time: f32,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Count nodes of each type over a region
+    Stats(StatsArgs),
+
+    /// List the nodes that differ between two worlds over a region
+    Diff(DiffArgs),
+
+    /// Export a block as a MagicaVoxel model
+    Vox(VoxArgs),
+
+    /// Export a region as a glTF scene
+    Gltf(GltfArgs),
+
+    /// Render a block to a PNG image without opening a window
+    Render(RenderArgs),
+
+    /// Print the node that `render` draws at a pixel
+    Sample(SampleArgs),
+
+    /// View a single block from a raw dump of its map data, without a world
+    ViewBlock(ViewBlockArgs),
+
+    /// List where a node appears over a region
+    Find(FindArgs),
+
+    /// Parse every block and report the ones that are broken
+    Check(CheckArgs),
+
+    /// Compress every block again at a chosen zstd level, to shrink the map
+    Recompress(RecompressArgs),
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// World directory
+    pub world: PathBuf,
+
+    /// Region in block coordinates, as `x0,y0,z0:x1,y1,z1`
+    #[arg(long, value_parser = parse_area, allow_hyphen_values = true)]
+    pub area: Area,
+
+    /// Write `node_name,count` rows to this file instead of printing them
+    #[arg(long, conflicts_with = "json")]
+    pub csv: Option<PathBuf>,
+
+    /// Print the counts as a JSON object mapping node names to counts
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// World directory to compare from, e.g. a backup
+    pub world_a: PathBuf,
+
+    /// World directory to compare to
+    pub world_b: PathBuf,
+
+    /// Region in block coordinates, as `x0,y0,z0:x1,y1,z1`
+    #[arg(long, value_parser = parse_area, allow_hyphen_values = true)]
+    pub area: Area,
+}
+
+#[derive(Args)]
+pub struct VoxArgs {
+    /// World directory
+    pub world: PathBuf,
+
+    /// Block to export, as `x,y,z` in block coordinates
+    #[arg(long, value_parser = parse_ivec3, allow_hyphen_values = true)]
+    pub block: IVec3,
+
+    /// Path of the `.vox` file to write
+    #[arg(long, short)]
+    pub output: PathBuf,
+
+    #[command(flatten)]
+    pub palette: PaletteArgs,
+
+    #[command(flatten)]
+    pub y_clip: YClipArgs,
+}
+
+#[derive(Args)]
+pub struct GltfArgs {
+    /// World directory
+    pub world: PathBuf,
+
+    /// Region in block coordinates, as `x0,y0,z0:x1,y1,z1`
+    #[arg(long, value_parser = parse_area, allow_hyphen_values = true)]
+    pub area: Area,
+
+    /// Path of the `.glb` file to write
+    #[arg(long, short)]
+    pub output: PathBuf,
+
+    /// Game directory holding a `nodes.json` dump of its node definitions
+    #[arg(long)]
+    pub game: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub palette: PaletteArgs,
+
+    #[command(flatten)]
+    pub y_clip: YClipArgs,
+}
+
+#[derive(Args)]
+pub struct YClipArgs {
+    /// Leave out nodes below this world Y
+    #[arg(long, allow_hyphen_values = true)]
+    pub y_min: Option<i32>,
+
+    /// Leave out nodes above this world Y
+    #[arg(long, allow_hyphen_values = true)]
+    pub y_max: Option<i32>,
+}
+
+#[derive(Args)]
+pub struct PaletteArgs {
+    /// How to pick node colors
+    #[arg(long, value_enum, default_value_t)]
+    pub color_scheme: ColorSchemeArg,
+
+    /// `colors.txt` to read node colors from, for `--color-scheme palette`
+    #[arg(long, required_if_eq("color_scheme", "palette"))]
+    pub colors: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum ColorSchemeArg {
+    /// A color per node name
+    #[default]
+    NameHash,
+    /// Similar colors for nodes from the same mod
+    CategoryHash,
+    /// Colors from a `colors.txt` file
+    Palette,
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// World directory
+    pub world: PathBuf,
+
+    /// Block to render, as `x,y,z` in block coordinates
+    #[arg(long, value_parser = parse_ivec3, allow_hyphen_values = true)]
+    pub block: IVec3,
+
+    /// Path of the PNG file to write
+    #[arg(long, short)]
+    pub output: PathBuf,
+
+    /// Camera position in nodes relative to the block's corner, as `x,y,z`; the camera
+    /// always looks at the block's center
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true, default_value = "28,24,28")]
+    pub camera: Vec3,
+
+    #[arg(long, default_value_t = 800)]
+    pub width: u32,
+
+    #[arg(long, default_value_t = 600)]
+    pub height: u32,
+
+    /// Render at this many times the resolution and average down, to smooth node edges
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=8))]
+    pub ssaa: u32,
+
+    /// Make nodes glow when their stored light is at least this level (0-15)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=15))]
+    pub emissive_threshold: Option<u8>,
+
+    /// Raymarch on the CPU instead of the GPU
+    #[arg(long)]
+    pub cpu: bool,
+
+    /// Game directory holding a `nodes.json` dump of its node definitions
+    #[arg(long)]
+    pub game: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub y_clip: YClipArgs,
+}
+
+#[derive(Args)]
+pub struct SampleArgs {
+    /// World directory
+    pub world: PathBuf,
+
+    /// Block to look at, as `x,y,z` in block coordinates
+    #[arg(long, value_parser = parse_ivec3, allow_hyphen_values = true)]
+    pub block: IVec3,
+
+    /// Pixel to sample, as `x,y` from the top left corner of the image
+    #[arg(long, value_parser = parse_uvec2)]
+    pub pixel: UVec2,
+
+    /// Camera position in nodes relative to the block's corner, as `x,y,z`; the camera
+    /// always looks at the block's center
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true, default_value = "28,24,28")]
+    pub camera: Vec3,
+
+    #[arg(long, default_value_t = 800)]
+    pub width: u32,
+
+    #[arg(long, default_value_t = 600)]
+    pub height: u32,
+
+    /// Game directory holding a `nodes.json` dump of its node definitions
+    #[arg(long)]
+    pub game: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub y_clip: YClipArgs,
+}
+
+#[derive(Args)]
+pub struct FindArgs {
+    /// World directory
+    pub world: PathBuf,
+
+    /// Node to look for, e.g. `default:mese`
+    pub node: String,
+
+    /// Region in block coordinates, as `x0,y0,z0:x1,y1,z1`
+    #[arg(long, value_parser = parse_area, allow_hyphen_values = true)]
+    pub area: Area,
+
+    /// Stop after this many nodes
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Print the positions as a JSON array of `[x, y, z]` arrays
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// World directory
+    pub world: PathBuf,
+
+    /// Region in block coordinates, as `x0,y0,z0:x1,y1,z1`; checks the whole map if omitted
+    #[arg(long, value_parser = parse_area, allow_hyphen_values = true)]
+    pub area: Option<Area>,
+
+    /// Print the broken blocks as a JSON array of `{ "pos": [x, y, z], "error" }` objects
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct ViewBlockArgs {
+    /// File holding the block's data exactly as stored in the map database
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub options: ViewerOptions,
+}
+
+#[derive(Args)]
+pub struct RecompressArgs {
+    /// World directory; it must not be open in a running server
+    pub world: PathBuf,
+
+    /// zstd compression level; Luanti itself uses 0, which means 3
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..=22))]
+    pub level: i32,
+
+    /// Region in block coordinates, as `x0,y0,z0:x1,y1,z1`; recompresses the whole map if
+    /// omitted
+    #[arg(long, value_parser = parse_area, allow_hyphen_values = true)]
+    pub area: Option<Area>,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+/// Safety switches shared by commands that modify a world.
+#[derive(Args)]
+pub struct WriteArgs {
+    /// Go through every step and report what would be written, without writing
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Actually write to the world; required unless `--dry-run` is given
+    #[arg(long, conflicts_with = "dry_run")]
+    pub force: bool,
+}
+
+fn parse_area(s: &str) -> Result<Area, String> {
+    let (a, b) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected `x0,y0,z0:x1,y1,z1`, got `{s}`"))?;
+
+    Ok(Area::new(parse_ivec3(a)?, parse_ivec3(b)?))
+}
+
+fn parse_ivec3(s: &str) -> Result<IVec3, String> {
+    let components = s
+        .split(',')
+        .map(|c| c.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid coordinate `{s}`: {err}"))?;
+
+    match components[..] {
+        [x, y, z] => Ok(IVec3::new(x, y, z)),
+        _ => Err(format!("expected three coordinates, got `{s}`")),
+    }
+}
+
+fn parse_uvec2(s: &str) -> Result<UVec2, String> {
+    let components = s
+        .split(',')
+        .map(|c| c.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid coordinate `{s}`: {err}"))?;
+
+    match components[..] {
+        [x, y] => Ok(UVec2::new(x, y)),
+        _ => Err(format!("expected two coordinates, got `{s}`")),
+    }
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let components = s
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid coordinate `{s}`: {err}"))?;
+
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("expected three coordinates, got `{s}`")),
+    }
+}
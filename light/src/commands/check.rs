@@ -0,0 +1,57 @@
+use std::error::Error;
+
+use serde_json::json;
+use world::{MapError, World};
+
+use crate::cli::CheckArgs;
+
+/// Prints `x,y,z: reason` for every block that fails to parse or refers to nodes missing
+/// from its mapping, or a JSON array of them with `--json`, and fails if any were found.
+pub fn run(args: CheckArgs) -> Result<(), Box<dyn Error>> {
+    let world = World::open(&args.world)?;
+
+    let positions = match args.area {
+        Some(area) => world.map.block_positions_in(area)?,
+        None => world.map.block_positions()?,
+    };
+
+    let mut checked = 0;
+    let mut broken = Vec::new();
+
+    for pos in positions {
+        let problem = match world.map.get_block(pos) {
+            Err(MapError::BlockNotFound) => continue,
+            Ok(block) => {
+                let ids = block.unmapped_ids();
+                (!ids.is_empty()).then(|| format!("node ids without a name: {ids:?}"))
+            }
+            Err(MapError::Parse(err)) => Some(err.to_string()),
+            Err(err) => return Err(err.into()),
+        };
+
+        checked += 1;
+
+        if let Some(problem) = problem {
+            if !args.json {
+                println!("{},{},{}: {problem}", pos.x, pos.y, pos.z);
+            }
+            broken.push((pos, problem));
+        }
+    }
+
+    if args.json {
+        let broken: Vec<_> = broken
+            .iter()
+            .map(|(pos, problem)| json!({ "pos": pos.to_array(), "error": problem }))
+            .collect();
+        println!("{}", serde_json::to_string(&broken)?);
+    }
+
+    eprintln!("checked {checked} blocks, {} broken", broken.len());
+
+    if !broken.is_empty() {
+        return Err(format!("found {} broken blocks", broken.len()).into());
+    }
+
+    Ok(())
+}
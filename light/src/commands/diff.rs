@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use glam::{IVec3, ivec3};
+use world::{Block, MapError, World};
+
+use crate::cli::DiffArgs;
+
+pub fn run(args: DiffArgs) -> Result<(), Box<dyn Error>> {
+    let world_a = World::open(&args.world_a)?;
+    let world_b = World::open(&args.world_b)?;
+
+    let mut differing_nodes = 0;
+    let mut differing_blocks = 0;
+
+    for block_pos in args.area.iter() {
+        let block_a = optional_block(world_a.map.get_block(block_pos))?;
+        let block_b = optional_block(world_b.map.get_block(block_pos))?;
+
+        let (block_a, block_b) = match (block_a, block_b) {
+            (Some(a), Some(b)) => (a, b),
+            (None, None) => continue,
+            (Some(_), None) => {
+                println!("block {block_pos}: only in {}", world_a.name);
+                differing_blocks += 1;
+                continue;
+            }
+            (None, Some(_)) => {
+                println!("block {block_pos}: only in {}", world_b.name);
+                differing_blocks += 1;
+                continue;
+            }
+        };
+
+        let changes = diff_blocks(&block_a, &block_b);
+        if changes.is_empty() {
+            continue;
+        }
+
+        differing_blocks += 1;
+        differing_nodes += changes.len();
+
+        for (pos, before, after) in changes {
+            let IVec3 { x, y, z } = block_pos * 16 + pos;
+            println!("{x},{y},{z}: {before} -> {after}");
+        }
+    }
+
+    println!("{differing_nodes} nodes differ across {differing_blocks} blocks");
+
+    Ok(())
+}
+
+fn optional_block(result: Result<Arc<Block>, MapError>) -> Result<Option<Arc<Block>>, MapError> {
+    match result {
+        Ok(block) => Ok(Some(block)),
+        Err(MapError::BlockNotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Lists the nodes whose type or param2 differ, with their names in both blocks.
+/// param1 is ignored since it mostly holds light, which changes without any edits.
+fn diff_blocks<'a>(a: &'a Block, b: &'a Block) -> Vec<(IVec3, &'a str, &'a str)> {
+    let mut changes = Vec::new();
+
+    for z in 0..16 {
+        for y in 0..16 {
+            for x in 0..16 {
+                let pos = ivec3(x, y, z);
+
+                let node_a = a.get_node(pos);
+                let node_b = b.get_node(pos);
+
+                let name_a = a.get_name_by_id(node_a.id).unwrap_or("unknown");
+                let name_b = b.get_name_by_id(node_b.id).unwrap_or("unknown");
+
+                if name_a != name_b || node_a.param2 != node_b.param2 {
+                    changes.push((pos, name_a, name_b));
+                }
+            }
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,24 @@
+use std::error::Error;
+
+use world::World;
+
+use crate::cli::FindArgs;
+
+pub fn run(args: FindArgs) -> Result<(), Box<dyn Error>> {
+    let world = World::open(&args.world)?;
+
+    let positions = world.map.find_nodes(args.area, &args.node, args.limit)?;
+
+    if args.json {
+        let positions: Vec<_> = positions.iter().map(|pos| pos.to_array()).collect();
+        println!("{}", serde_json::to_string(&positions)?);
+    } else {
+        for pos in &positions {
+            println!("{},{},{}", pos.x, pos.y, pos.z);
+        }
+    }
+
+    eprintln!("{} nodes of {} found", positions.len(), args.node);
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use asset::Mesh;
+use render::meshing::make_meshes_by_node;
+use world::World;
+
+use crate::cli::GltfArgs;
+use crate::node::{default_palette, load_node_defs};
+
+/// Exports the region as a single scene, positioned relative to the region's lowest corner.
+///
+/// Blocks are meshed one at a time, so faces between two neighbouring blocks are kept.
+pub fn run(args: GltfArgs) -> Result<(), Box<dyn Error>> {
+    let world = World::open(&args.world)?;
+    let node_defs = load_node_defs(args.game.as_deref())?;
+
+    let mut meshes: HashMap<String, Mesh> = HashMap::new();
+    let mut block_count = 0;
+
+    for (block_pos, block) in world.map.blocks_in(args.area) {
+        let offset = ((block_pos - args.area.min) * 16).as_vec3();
+
+        let mut block = block?;
+        super::clip_block(&mut block, block_pos, &args.y_clip);
+
+        for (name, mesh) in make_meshes_by_node(&block, &node_defs) {
+            meshes.entry(name).or_default().append(&mesh, offset);
+        }
+
+        block_count += 1;
+    }
+
+    let scheme = super::color_scheme(&args.palette)?;
+    let color = default_palette(&scheme);
+
+    // sorted so that materials come out in the same order on every run
+    let mut meshes: Vec<_> = meshes.iter().collect();
+    meshes.sort_by_key(|(name, _)| name.as_str());
+
+    let writer = BufWriter::new(File::create(&args.output)?);
+    asset::export_gltf(
+        meshes.into_iter().map(|(name, mesh)| (name.as_str(), mesh)),
+        |name| Some(color(name)),
+        writer,
+    )?;
+
+    println!("wrote {block_count} blocks to {}", args.output.display());
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use glam::{IVec3, ivec3};
+use world::{Block, Node};
+
+use crate::cli::{ColorSchemeArg, PaletteArgs, WriteArgs, YClipArgs};
+use crate::node::ColorScheme;
+
+pub mod check;
+pub mod diff;
+pub mod find;
+pub mod gltf;
+pub mod recompress;
+pub mod render;
+pub mod sample;
+pub mod stats;
+pub mod vox;
+
+/// Refuses to go on with a command that writes to the world at `world` unless it was
+/// given `--dry-run` or `--force`.
+fn confirm_write(args: &WriteArgs, world: &Path) -> Result<(), String> {
+    if args.dry_run {
+        return Ok(());
+    }
+
+    if !args.force {
+        return Err(format!(
+            "this modifies {}; check with --dry-run first, then pass --force to write",
+            world.display()
+        ));
+    }
+
+    eprintln!(
+        "writing to {}; stop any server using it and keep a backup",
+        world.display()
+    );
+
+    Ok(())
+}
+
+fn color_scheme(args: &PaletteArgs) -> io::Result<ColorScheme> {
+    Ok(match args.color_scheme {
+        ColorSchemeArg::NameHash => ColorScheme::NameHash,
+        ColorSchemeArg::CategoryHash => ColorScheme::CategoryHash,
+        ColorSchemeArg::Palette => ColorScheme::load_palette(args.colors.as_ref().unwrap())?,
+    })
+}
+
+/// Replaces the nodes outside the Y range of `clip` with air, for the block at `block_pos`.
+/// The block is only copied out of the map's cache if there is anything to clip.
+fn clip_block(block: &mut Arc<Block>, block_pos: IVec3, clip: &YClipArgs) {
+    if clip.y_min.is_none() && clip.y_max.is_none() {
+        return;
+    }
+
+    let block = Arc::make_mut(block);
+
+    let air = Node {
+        id: block.get_or_insert_id("air"),
+        param1: 0,
+        param2: 0,
+    };
+
+    for y in 0..16 {
+        let world_y = block_pos.y * 16 + y;
+        let below = clip.y_min.is_some_and(|min| world_y < min);
+        let above = clip.y_max.is_some_and(|max| world_y > max);
+        if !below && !above {
+            continue;
+        }
+
+        for z in 0..16 {
+            for x in 0..16 {
+                block.set_node(ivec3(x, y, z), air);
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::error::Error;
+
+use glam::IVec3;
+use world::{BlockParser, MapError, Timestamp, World};
+
+use crate::cli::RecompressArgs;
+
+/// Blocks written per transaction.
+const BATCH_SIZE: usize = 256;
+
+/// Re-encodes every block at `--level`, writing back the ones that got smaller, and prints
+/// how much space that saved.
+///
+/// Each re-encoded block is parsed again and compared with the original before it is
+/// written, and blocks that fail to parse are left alone.
+pub fn run(args: RecompressArgs) -> Result<(), Box<dyn Error>> {
+    let world = World::open(&args.world)?;
+
+    let positions = match args.area {
+        Some(area) => world.map.block_positions_in(area)?,
+        None => world.map.block_positions()?,
+    };
+
+    super::confirm_write(&args.write, &args.world)?;
+    let dry_run = args.write.dry_run;
+
+    let mut parser = BlockParser::new();
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut blocks = 0;
+    let mut skipped = 0;
+    let mut size_before = 0;
+    let mut size_after = 0;
+
+    for (index, &pos) in positions.iter().enumerate() {
+        let data = match world.map.get_block_data(pos) {
+            Ok(data) => data,
+            Err(MapError::BlockNotFound) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        blocks += 1;
+        size_before += data.len();
+
+        match recompress(&mut parser, &data, args.level) {
+            Ok(Some(recompressed)) => {
+                if dry_run {
+                    println!(
+                        "{},{},{}: {} -> {} bytes",
+                        pos.x,
+                        pos.y,
+                        pos.z,
+                        data.len(),
+                        recompressed.len()
+                    );
+                }
+                size_after += recompressed.len();
+                batch.push((pos, recompressed));
+            }
+            Ok(None) => size_after += data.len(),
+            Err(err) => {
+                eprintln!("{},{},{}: skipped, {err}", pos.x, pos.y, pos.z);
+                skipped += 1;
+                size_after += data.len();
+            }
+        }
+
+        if batch.len() == BATCH_SIZE {
+            write_batch(&world, &mut batch, dry_run)?;
+        }
+
+        if (index + 1) % 1000 == 0 {
+            eprintln!("{}/{} positions", index + 1, positions.len());
+        }
+    }
+
+    write_batch(&world, &mut batch, dry_run)?;
+
+    let saved = size_before - size_after;
+    let percent = saved as f64 / size_before.max(1) as f64 * 100.0;
+    println!(
+        "{} {blocks} blocks: {size_before} -> {size_after} bytes, {saved} bytes ({percent:.1}%) saved",
+        if dry_run {
+            "would recompress"
+        } else {
+            "recompressed"
+        },
+    );
+
+    if skipped > 0 {
+        eprintln!("skipped {skipped} blocks that failed to parse");
+    }
+
+    if !dry_run && saved > 0 {
+        eprintln!("sqlite keeps the freed space until the map file is VACUUMed");
+    }
+
+    Ok(())
+}
+
+/// Returns `data` compressed at `level`, or `None` if that isn't any smaller.
+fn recompress(
+    parser: &mut BlockParser,
+    data: &[u8],
+    level: i32,
+) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let block = parser.parse(data)?;
+    let recompressed = block.serialize_with_level(Timestamp::Preserve, level)?;
+
+    if recompressed.len() >= data.len() {
+        return Ok(None);
+    }
+
+    if parser.parse(&recompressed)? != block {
+        return Err("the recompressed block reads back differently".into());
+    }
+
+    Ok(Some(recompressed))
+}
+
+fn write_batch(
+    world: &World,
+    batch: &mut Vec<(IVec3, Vec<u8>)>,
+    dry_run: bool,
+) -> Result<(), MapError> {
+    if !dry_run {
+        world.map.set_blocks_data(batch)?;
+    }
+
+    batch.clear();
+
+    Ok(())
+}
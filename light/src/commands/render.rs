@@ -0,0 +1,107 @@
+use std::error::Error;
+
+use asset::Mesh;
+use glam::Vec3;
+use image::{Rgba, RgbaImage};
+use render::meshing::make_mesh;
+use world::{MapError, World};
+
+use crate::camera::Camera;
+use crate::cli::RenderArgs;
+use crate::grid::GridDims;
+use crate::node::{GlobalMapping, load_node_defs};
+use crate::render::Renderer;
+use crate::{cpu_render, lod};
+
+pub fn run(args: RenderArgs) -> Result<(), Box<dyn Error>> {
+    let world = World::open(&args.world)?;
+    let node_defs = load_node_defs(args.game.as_deref())?;
+    let mut block = match world.map.get_block(args.block) {
+        Ok(block) => Some(block),
+        Err(MapError::BlockNotFound) => None,
+        Err(err) => return Err(err.into()),
+    };
+
+    if let Some(block) = &mut block {
+        super::clip_block(block, args.block, &args.y_clip);
+    }
+
+    let mut global_mapping = GlobalMapping::new();
+    global_mapping.get_or_insert_id("air");
+    let grid = match &block {
+        Some(block) => crate::grid::block_to_grid(block, &mut global_mapping),
+        None => vec![0; 16 * 16 * 16],
+    };
+
+    // An empty image looks like a rendering failure, so say why it is empty.
+    if grid.iter().all(|&value| value >> 16 == 0) {
+        eprintln!(
+            "no nodes found in block {}; the world has {}",
+            args.block,
+            world.map.summary()?
+        );
+    }
+
+    let mut camera = Camera::new().with_position(args.camera);
+    camera.look_at(Vec3::splat(8.0));
+
+    let dims = GridDims::splat(16);
+    let node_flags = global_mapping.node_flags(&node_defs);
+    let (width, height) = (args.width * args.ssaa, args.height * args.ssaa);
+
+    let image = if args.cpu {
+        cpu_render::render(
+            &camera,
+            &grid,
+            dims,
+            &node_flags,
+            args.emissive_threshold,
+            width,
+            height,
+        )
+    } else {
+        let mut renderer = Renderer::new_headless(width, height)?;
+        renderer.set_node_flags(&node_flags);
+        renderer.set_emissive_threshold(args.emissive_threshold);
+
+        let pyramid = lod::build_pyramid(&grid, dims);
+        let data = renderer.create_data_buffer(args.block, dims, bytemuck::cast_slice(&pyramid));
+        let mesh = match &block {
+            Some(block) => make_mesh(block, &node_defs),
+            None => Mesh::new(),
+        };
+        let mesh = renderer.create_mesh_buffer(&mesh);
+
+        renderer.render(&camera, &data, &mesh)?;
+        renderer.capture_frame()
+    };
+    let image = downsample(image, args.ssaa);
+    image.save(&args.output)?;
+
+    println!("wrote block {} to {}", args.block, args.output.display());
+
+    Ok(())
+}
+
+/// Averages each `factor`×`factor` square of pixels into one.
+fn downsample(image: RgbaImage, factor: u32) -> RgbaImage {
+    if factor == 1 {
+        return image;
+    }
+
+    let samples = factor * factor;
+
+    RgbaImage::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut sum = [0u32; 4];
+        for dy in 0..factor {
+            for dx in 0..factor {
+                let pixel = image.get_pixel(x * factor + dx, y * factor + dy);
+                for (sum, channel) in sum.iter_mut().zip(pixel.0) {
+                    *sum += u32::from(channel);
+                }
+            }
+        }
+
+        Rgba(sum.map(|sum| ((sum + samples / 2) / samples) as u8))
+    })
+}
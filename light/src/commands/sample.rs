@@ -0,0 +1,47 @@
+use std::error::Error;
+
+use glam::Vec3;
+use world::World;
+
+use crate::camera::Camera;
+use crate::cli::SampleArgs;
+use crate::grid::GridDims;
+use crate::lod;
+use crate::node::{GlobalMapping, load_node_defs};
+use crate::render::Renderer;
+
+pub fn run(args: SampleArgs) -> Result<(), Box<dyn Error>> {
+    if args.pixel.x >= args.width || args.pixel.y >= args.height {
+        return Err(format!(
+            "pixel {} is outside the {}x{} image",
+            args.pixel, args.width, args.height
+        )
+        .into());
+    }
+
+    let world = World::open(&args.world)?;
+    let node_defs = load_node_defs(args.game.as_deref())?;
+    let mut block = world.map.get_block(args.block)?;
+    super::clip_block(&mut block, args.block, &args.y_clip);
+
+    let mut global_mapping = GlobalMapping::new();
+    global_mapping.get_or_insert_id("air");
+    let grid = crate::grid::block_to_grid(&block, &mut global_mapping);
+
+    let mut camera = Camera::new().with_position(args.camera);
+    camera.look_at(Vec3::splat(8.0));
+
+    let dims = GridDims::splat(16);
+    let mut renderer = Renderer::new_headless(args.width, args.height)?;
+    renderer.set_node_flags(&global_mapping.node_flags(&node_defs));
+
+    let pyramid = lod::build_pyramid(&grid, dims);
+    let data = renderer.create_data_buffer(args.block, dims, bytemuck::cast_slice(&pyramid));
+
+    match renderer.sample_node_at(&camera, &data, (args.pixel.x, args.pixel.y)) {
+        Some(id) => println!("{}", global_mapping.name(id).unwrap_or("unknown")),
+        None => println!("nothing at pixel {}", args.pixel),
+    }
+
+    Ok(())
+}
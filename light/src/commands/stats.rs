@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use rayon::prelude::*;
+use serde_json::json;
+use world::{MapError, World};
+
+use crate::cli::StatsArgs;
+
+pub fn run(args: StatsArgs) -> Result<(), Box<dyn Error>> {
+    let world = World::open(&args.world)?;
+
+    // The backend hands out one block at a time, but parsing and counting them is spread
+    // over the thread pool.
+    let counts = world
+        .map
+        .block_positions_in(args.area)?
+        .into_par_iter()
+        .try_fold(HashMap::new, |mut counts: HashMap<String, u64>, pos| {
+            let block = match world.map.get_block(pos) {
+                Err(MapError::BlockNotFound) => return Ok(counts),
+                block => block?,
+            };
+
+            for (name, count) in block.node_counts() {
+                *counts.entry(name.to_owned()).or_insert(0) += count as u64;
+            }
+
+            Ok::<_, MapError>(counts)
+        })
+        .try_reduce(HashMap::new, |mut counts, other| {
+            for (name, count) in other {
+                *counts.entry(name).or_insert(0) += count;
+            }
+
+            Ok(counts)
+        })?;
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a_name, a_count), (b_name, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_name.cmp(b_name))
+    });
+
+    if args.json {
+        let counts: serde_json::Map<_, _> = counts
+            .into_iter()
+            .map(|(name, count)| (name, json!(count)))
+            .collect();
+        println!("{}", serde_json::Value::Object(counts));
+
+        return Ok(());
+    }
+
+    match args.csv {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path)?);
+
+            writeln!(writer, "node_name,count")?;
+            for (name, count) in &counts {
+                writeln!(writer, "{name},{count}")?;
+            }
+
+            writer.flush()?;
+        }
+        None => {
+            for (name, count) in &counts {
+                println!("{count:>12} {name}");
+            }
+        }
+    }
+
+    Ok(())
+}
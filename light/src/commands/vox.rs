@@ -0,0 +1,29 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use world::World;
+
+use crate::cli::VoxArgs;
+use crate::node::default_palette;
+
+pub fn run(args: VoxArgs) -> Result<(), Box<dyn Error>> {
+    let world = World::open(&args.world)?;
+    let mut block = world.map.get_block(args.block)?;
+    super::clip_block(&mut block, args.block, &args.y_clip);
+
+    let scheme = super::color_scheme(&args.palette)?;
+    let color = default_palette(&scheme);
+
+    let palette = |name: &str| match name {
+        "air" | "ignore" => None,
+        _ => Some(color(name)),
+    };
+
+    let writer = BufWriter::new(File::create(&args.output)?);
+    asset::export_vox(&block, palette, writer)?;
+
+    println!("wrote block {} to {}", args.block, args.output.display());
+
+    Ok(())
+}
@@ -0,0 +1,307 @@
+use glam::{BVec3, IVec3, UVec2, Vec2, Vec3, uvec2, vec2, vec3};
+use image::RgbaImage;
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::grid::GridDims;
+use crate::node::{NODE_HIDDEN, NODE_SEE_THROUGH};
+
+const EMISSIVE_COLOR: Vec3 = vec3(1.0, 0.75, 0.4);
+const SEE_THROUGH_COLOR: Vec3 = vec3(0.7, 0.85, 0.9);
+
+/// Raymarches a grid on the CPU, one pixel per rayon task.
+///
+/// This is a port of the raymarch path of `shader.wgsl` at full detail, function for
+/// function, so it can stand in for the GPU on machines without one and serve as a
+/// reference image for the shader. Flooding and detail levels are not supported. Keep the
+/// two in sync when changing either.
+pub fn render(
+    camera: &Camera,
+    grid: &[u32],
+    dims: GridDims,
+    node_flags: &[u32],
+    emissive_threshold: Option<u8>,
+    width: u32,
+    height: u32,
+) -> RgbaImage {
+    let (forward, _) = camera.forward_right();
+    let fov = camera.fov.to_radians();
+    let aspect_ratio = width as f32 / height as f32;
+    // light levels never exceed 15, so 16 disables emission
+    let emissive_threshold = emissive_threshold.map_or(16, u32::from);
+
+    let mut image = RgbaImage::new(width, height);
+
+    image
+        .par_chunks_mut(4)
+        .enumerate()
+        .for_each(|(index, pixel)| {
+            let pixel_pos = uvec2(index as u32 % width, index as u32 / width);
+            let texcoord = pixel_texcoord(pixel_pos, width, height);
+
+            let dir = get_ray_dir(forward, fov, aspect_ratio, texcoord);
+            let ray = Ray::new(camera.position, dir);
+            let color = shade(ray, grid, dims, node_flags, emissive_threshold);
+
+            pixel.copy_from_slice(&[
+                linear_to_srgb(color.x),
+                linear_to_srgb(color.y),
+                linear_to_srgb(color.z),
+                255,
+            ]);
+        });
+
+    image
+}
+
+/// Returns the grid position of the first visible node in the middle of the camera's view,
+/// as drawn by [`render`].
+pub fn pick(camera: &Camera, grid: &[u32], dims: GridDims, node_flags: &[u32]) -> Option<IVec3> {
+    let (forward, _) = camera.forward_right();
+
+    first_hit(Ray::new(camera.position, forward), grid, dims, node_flags)
+}
+
+// the fullscreen triangle's texcoords run from 0 at the bottom left to 2 at the top right
+// of the screen
+fn pixel_texcoord(pixel: UVec2, width: u32, height: u32) -> Vec2 {
+    vec2(
+        (pixel.x as f32 + 0.5) / width as f32 * 2.0,
+        2.0 - (pixel.y as f32 + 0.5) / height as f32 * 2.0,
+    )
+}
+
+fn first_hit(mut ray: Ray, grid: &[u32], dims: GridDims, node_flags: &[u32]) -> Option<IVec3> {
+    let half_size = dims.as_vec3() / 2.0;
+    let box_dist = s_box(&ray, half_size, half_size);
+    if box_dist > 0.0 {
+        ray.origin += ray.dir * (box_dist - 0.1);
+    }
+
+    let hit = block_dda(&ray, grid, dims, node_flags, &mut 0)?;
+    let hit_point = ray.origin + hit.distance * ray.dir;
+
+    Some((hit_point - hit.normal * 0.5).floor().as_ivec3())
+}
+
+fn shade(
+    mut ray: Ray,
+    grid: &[u32],
+    dims: GridDims,
+    node_flags: &[u32],
+    emissive_threshold: u32,
+) -> Vec3 {
+    let half_size = dims.as_vec3() / 2.0;
+    let box_dist = s_box(&ray, half_size, half_size);
+    if box_dist > 0.0 {
+        ray.origin += ray.dir * (box_dist - 0.1);
+    }
+
+    let mut see_through_layers = 0;
+    let color = match block_dda(&ray, grid, dims, node_flags, &mut see_through_layers) {
+        Some(hit) => shade_hit(&ray, &hit, grid, dims, emissive_threshold),
+        None => Vec3::ZERO,
+    };
+
+    color.lerp(
+        SEE_THROUGH_COLOR,
+        1.0 - 0.75f32.powi(see_through_layers as i32),
+    )
+}
+
+fn shade_hit(ray: &Ray, hit: &Hit, grid: &[u32], dims: GridDims, emissive_threshold: u32) -> Vec3 {
+    let sun_dir = vec3(0.5, 0.7, 1.0).normalize();
+    let light = hit.normal.dot(sun_dir).max(0.2).clamp(0.0, 1.0);
+    let mut color = vec3(light, 0.0, 0.0);
+
+    if night_light(hit.voxel) >= emissive_threshold {
+        color += EMISSIVE_COLOR;
+    } else if emissive_threshold <= 15 {
+        let hit_point = ray.origin + hit.distance * ray.dir;
+        let front = fetch_voxel(
+            grid,
+            dims,
+            (hit_point + hit.normal * 0.5).floor().as_ivec3(),
+        );
+        let glow = night_light(front) as f32 / 15.0;
+        color += EMISSIVE_COLOR * glow * glow * 0.6;
+    }
+
+    color
+}
+
+struct Ray {
+    origin: Vec3,
+    dir: Vec3,
+    inv_dir: Vec3,
+}
+
+impl Ray {
+    fn new(origin: Vec3, dir: Vec3) -> Self {
+        Self {
+            origin,
+            dir,
+            inv_dir: dir.recip(),
+        }
+    }
+}
+
+struct Hit {
+    distance: f32,
+    normal: Vec3,
+    voxel: u32,
+}
+
+fn get_ray_dir(forward: Vec3, fov: f32, aspect_ratio: f32, texcoord: Vec2) -> Vec3 {
+    let horizontal = forward.cross(Vec3::Y);
+    let vertical = horizontal.cross(forward);
+
+    let tan_half_fov = (fov / 2.0).tan();
+
+    let x = (texcoord.x - 1.0) * horizontal * 2.0 * tan_half_fov * aspect_ratio;
+    let y = (texcoord.y - 1.0) * vertical * 2.0 * tan_half_fov;
+
+    (forward + x + y).normalize()
+}
+
+fn block_dda(
+    ray: &Ray,
+    grid: &[u32],
+    dims: GridDims,
+    node_flags: &[u32],
+    see_through_layers: &mut u32,
+) -> Option<Hit> {
+    let size = dims.as_ivec3();
+    let mut dda = DdaState::new(ray);
+    let mut previous_id = 0;
+
+    // a ray crosses at most this many cells on its way through the grid
+    for _ in 0..size.element_sum() {
+        dda.step();
+        let voxel = fetch_voxel(grid, dims, dda.voxel_pos);
+
+        let id = voxel >> 16;
+        let flags = node_flags.get(id as usize).copied().unwrap_or(0);
+        if id != 0 && flags & NODE_HIDDEN == 0 {
+            if flags & NODE_SEE_THROUGH == 0 {
+                let (distance, normal) = dda.end(ray);
+                return Some(Hit {
+                    distance,
+                    normal,
+                    voxel,
+                });
+            }
+
+            if id != previous_id {
+                *see_through_layers += 1;
+            }
+        }
+        previous_id = id;
+
+        if dda.voxel_pos.cmpgt(size).any() || dda.voxel_pos.cmplt(IVec3::splat(-1)).any() {
+            break;
+        }
+    }
+
+    None
+}
+
+struct DdaState {
+    voxel_pos: IVec3,
+    d_dist: Vec3,
+    ray_step: IVec3,
+    dist: Vec3,
+    mask: BVec3,
+}
+
+impl DdaState {
+    fn new(ray: &Ray) -> Self {
+        let voxel_pos = ray.origin.floor().as_ivec3();
+        let d_dist = (ray.dir.length() * ray.inv_dir).abs();
+        let s = ray.dir.signum();
+        let dist = (s * (voxel_pos.as_vec3() - ray.origin) + (s * 0.5) + 0.5) * d_dist;
+
+        Self {
+            voxel_pos,
+            d_dist,
+            ray_step: s.as_ivec3(),
+            dist,
+            mask: BVec3::FALSE,
+        }
+    }
+
+    fn step(&mut self) {
+        let lt = BVec3::new(
+            self.dist.x < self.dist.y,
+            self.dist.x < self.dist.z,
+            self.dist.y < self.dist.z,
+        );
+
+        if lt.x && lt.y {
+            self.dist.x += self.d_dist.x;
+            self.voxel_pos.x += self.ray_step.x;
+            self.mask = BVec3::new(true, false, false);
+        } else if !lt.x && lt.z {
+            self.dist.y += self.d_dist.y;
+            self.voxel_pos.y += self.ray_step.y;
+            self.mask = BVec3::new(false, true, false);
+        } else {
+            self.dist.z += self.d_dist.z;
+            self.voxel_pos.z += self.ray_step.z;
+            self.mask = BVec3::new(false, false, true);
+        }
+    }
+
+    fn end(&self, ray: &Ray) -> (f32, Vec3) {
+        let normal = Vec3::from(self.mask) * -ray.dir.signum();
+        let mini = (self.voxel_pos.as_vec3() - ray.origin + 0.5 - 0.5 * self.ray_step.as_vec3())
+            * ray.inv_dir;
+
+        (mini.max_element(), normal)
+    }
+}
+
+// param1 keeps sunlight in the low nibble and artificial light in the high one
+fn night_light(voxel: u32) -> u32 {
+    (voxel >> 12) & 0xF
+}
+
+fn fetch_voxel(grid: &[u32], dims: GridDims, pos: IVec3) -> u32 {
+    let size = dims.as_ivec3();
+    let in_bounds = pos.cmplt(size).all() && pos.cmpge(IVec3::ZERO).all();
+    if !in_bounds {
+        return 0;
+    }
+
+    grid[(pos.x + pos.y * size.x + pos.z * size.x * size.y) as usize]
+}
+
+fn s_box(ray: &Ray, center: Vec3, radius: Vec3) -> f32 {
+    let ro = ray.origin - center;
+    let m = ray.dir.recip();
+    let n = m * ro;
+    let k = m.abs() * radius;
+
+    let t1 = -n - k;
+    let t2 = -n + k;
+
+    let t_near = t1.max_element();
+    let t_far = t2.min_element();
+    if t_near > t_far || t_far < 0.0 {
+        return -1.0;
+    }
+
+    t_near
+}
+
+/// The GPU writes to an sRGB surface, which encodes the shader's linear output.
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * 255.0).round() as u8
+}
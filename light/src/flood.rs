@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+
+use glam::{IVec3, ivec3};
+
+use crate::grid::GridDims;
+
+/// Marks the air cells of a grid that a water surface at local height `level` would fill.
+///
+/// Water enters through the sides of the grid and spreads through air cells below `level`,
+/// so enclosed air pockets (cellars, caves without an opening) stay dry. The result holds
+/// one value per cell in grid order: 1 for water, 0 otherwise.
+pub fn flood_fill(grid: &[u32], dims: GridDims, level: i32) -> Vec<u32> {
+    let size = dims.as_ivec3();
+    let index = |pos: IVec3| (pos.z * size.y * size.x + pos.y * size.x + pos.x) as usize;
+
+    let mut flooded = vec![0; grid.len()];
+    let mut queue = VecDeque::new();
+
+    let is_open = |pos: IVec3| {
+        pos.cmpge(IVec3::ZERO).all()
+            && pos.cmplt(size).all()
+            && pos.y < level
+            && grid[index(pos)] >> 16 == 0
+    };
+
+    for y in 0..size.y {
+        let sides = (0..size.x)
+            .flat_map(|x| [ivec3(x, y, 0), ivec3(x, y, size.z - 1)])
+            .chain((0..size.z).flat_map(|z| [ivec3(0, y, z), ivec3(size.x - 1, y, z)]));
+
+        for pos in sides {
+            if is_open(pos) && flooded[index(pos)] == 0 {
+                flooded[index(pos)] = 1;
+                queue.push_back(pos);
+            }
+        }
+    }
+
+    while let Some(pos) = queue.pop_front() {
+        for dir in [
+            IVec3::X,
+            IVec3::NEG_X,
+            IVec3::Y,
+            IVec3::NEG_Y,
+            IVec3::Z,
+            IVec3::NEG_Z,
+        ] {
+            let next = pos + dir;
+
+            if is_open(next) && flooded[index(next)] == 0 {
+                flooded[index(next)] = 1;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    flooded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 1 << 16;
+
+    #[test]
+    fn enclosed_pockets_stay_dry() {
+        let dims = GridDims::splat(6);
+        let index = |x: u32, y: u32, z: u32| (z * 36 + y * 6 + x) as usize;
+
+        // A hollow stone cube with one air cell inside it, at (2, 2, 2).
+        let mut grid = vec![0; 6 * 6 * 6];
+        for z in 1..=3 {
+            for y in 1..=3 {
+                for x in 1..=3 {
+                    if (x, y, z) != (2, 2, 2) {
+                        grid[index(x, y, z)] = STONE;
+                    }
+                }
+            }
+        }
+
+        let flooded = flood_fill(&grid, dims, 5);
+
+        assert_eq!(flooded[index(2, 2, 2)], 0);
+        assert_eq!(flooded[index(1, 1, 1)], 0);
+        assert_eq!(flooded[index(0, 0, 0)], 1);
+        assert_eq!(flooded[index(4, 4, 4)], 1);
+        assert_eq!(flooded[index(2, 4, 2)], 1);
+        assert_eq!(flooded[index(2, 5, 2)], 0);
+    }
+}
@@ -0,0 +1,183 @@
+use glam::{IVec3, UVec3, ivec3};
+use world::Block;
+
+use crate::node::GlobalMapping;
+
+const BLOCK_SIZE: i32 = 16;
+
+/// Size of a packed grid in nodes along each axis.
+pub type GridDims = UVec3;
+
+/// Packs a block into the grid format the raymarcher reads: one `u32` per node holding the
+/// global id in the high 16 bits, then param1 and param2, in x-major order.
+pub fn block_to_grid(block: &Block, global_mapping: &mut GlobalMapping) -> Vec<u32> {
+    let mut data = vec![0; 16 * 16 * 16];
+    write_block(
+        &mut data,
+        GridDims::splat(16),
+        IVec3::ZERO,
+        block,
+        global_mapping,
+    );
+
+    data
+}
+
+/// Packs several blocks into one grid covering their bounding box, so they can be uploaded
+/// and drawn as a single buffer.
+///
+/// Returns the position of the grid's lowest corner in block coordinates, the grid's size
+/// in nodes, and the packed nodes, laid out like [`block_to_grid`] but with the grid's
+/// size as the row and slice length. Blocks missing from `blocks` are left as air.
+pub fn merge_blocks(
+    blocks: &[(IVec3, &Block)],
+    global_mapping: &mut GlobalMapping,
+) -> (IVec3, GridDims, Vec<u32>) {
+    let Some(min) = blocks.iter().map(|(pos, _)| *pos).reduce(IVec3::min) else {
+        return (IVec3::ZERO, GridDims::ZERO, Vec::new());
+    };
+    let max = blocks
+        .iter()
+        .map(|(pos, _)| *pos)
+        .reduce(IVec3::max)
+        .unwrap();
+
+    let dims = ((max - min + 1) * BLOCK_SIZE).as_uvec3();
+    let mut data = vec![0; dims.element_product() as usize];
+
+    for (pos, block) in blocks {
+        let offset = (pos - min) * BLOCK_SIZE;
+        write_block(&mut data, dims, offset, block, global_mapping);
+    }
+
+    (min, dims, data)
+}
+
+fn write_block(
+    data: &mut [u32],
+    dims: GridDims,
+    offset: IVec3,
+    block: &Block,
+    global_mapping: &mut GlobalMapping,
+) {
+    for z in 0..BLOCK_SIZE {
+        for y in 0..BLOCK_SIZE {
+            for x in 0..BLOCK_SIZE {
+                let node = block.get_node(ivec3(x, y, z));
+                let name = block.get_name_by_id(node.id).unwrap();
+                let global_id = global_mapping.get_or_insert_id(name);
+
+                let mut value = 0;
+                value |= (global_id as u32) << 16;
+                value |= (node.param1 as u32) << 8;
+                value |= node.param2 as u32;
+
+                let pos = (offset + ivec3(x, y, z)).as_uvec3();
+                let index = (pos.z * dims.y * dims.x + pos.y * dims.x + pos.x) as usize;
+                data[index] = value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::uvec3;
+    use world::Node;
+
+    use super::*;
+
+    fn block_with(nodes: &[(IVec3, &str, u8)]) -> Block {
+        let mut block = Block::new();
+
+        for &(pos, name, param2) in nodes {
+            let id = block.get_or_insert_id(name);
+            block.set_node(
+                pos,
+                Node {
+                    id,
+                    param1: 0,
+                    param2,
+                },
+            );
+        }
+
+        block
+    }
+
+    fn mapping() -> GlobalMapping {
+        let mut mapping = GlobalMapping::new();
+        mapping.get_or_insert_id("air");
+        mapping
+    }
+
+    fn at(data: &[u32], dims: GridDims, pos: UVec3) -> u32 {
+        data[(pos.z * dims.y * dims.x + pos.y * dims.x + pos.x) as usize]
+    }
+
+    #[test]
+    fn single_block_matches_block_to_grid() {
+        let block = block_with(&[(ivec3(1, 2, 3), "default:stone", 5)]);
+
+        let expected = block_to_grid(&block, &mut mapping());
+        let (origin, dims, data) = merge_blocks(&[(ivec3(4, -2, 7), &block)], &mut mapping());
+
+        assert_eq!(origin, ivec3(4, -2, 7));
+        assert_eq!(dims, uvec3(16, 16, 16));
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn places_blocks_by_position() {
+        let blocks = [
+            (ivec3(0, 0, 0), block_with(&[(ivec3(0, 0, 0), "a", 1)])),
+            (ivec3(1, 0, 0), block_with(&[(ivec3(15, 0, 0), "b", 2)])),
+            (ivec3(0, 1, 0), block_with(&[(ivec3(0, 15, 0), "c", 3)])),
+            (ivec3(1, 1, 1), block_with(&[(ivec3(15, 15, 15), "d", 4)])),
+        ];
+
+        let mut mapping = mapping();
+        let blocks: Vec<_> = blocks.iter().map(|(pos, block)| (*pos, block)).collect();
+        let (origin, dims, data) = merge_blocks(&blocks, &mut mapping);
+
+        assert_eq!(origin, IVec3::ZERO);
+        assert_eq!(dims, uvec3(32, 32, 32));
+        assert_eq!(data.len(), 32 * 32 * 32);
+
+        let mut packed = |name, param2: u32| (mapping.get_or_insert_id(name) as u32) << 16 | param2;
+        assert_eq!(at(&data, dims, uvec3(0, 0, 0)), packed("a", 1));
+        assert_eq!(at(&data, dims, uvec3(31, 0, 0)), packed("b", 2));
+        assert_eq!(at(&data, dims, uvec3(0, 31, 0)), packed("c", 3));
+        assert_eq!(at(&data, dims, uvec3(31, 31, 31)), packed("d", 4));
+
+        let non_air = data.iter().filter(|&&value| value >> 16 != 0).count();
+        assert_eq!(non_air, 4);
+    }
+
+    #[test]
+    fn missing_blocks_are_air() {
+        let blocks = [
+            (ivec3(-1, 0, 0), block_with(&[(ivec3(0, 0, 0), "a", 0)])),
+            (ivec3(1, 0, 2), block_with(&[(ivec3(0, 0, 0), "a", 0)])),
+        ];
+
+        let blocks: Vec<_> = blocks.iter().map(|(pos, block)| (*pos, block)).collect();
+        let (origin, dims, data) = merge_blocks(&blocks, &mut mapping());
+
+        assert_eq!(origin, ivec3(-1, 0, 0));
+        assert_eq!(dims, uvec3(48, 16, 48));
+
+        let non_air: Vec<_> = (0..data.len())
+            .filter(|&index| data[index] >> 16 != 0)
+            .collect();
+        assert_eq!(non_air, [0, 32 + 32 * 16 * 48]);
+    }
+
+    #[test]
+    fn no_blocks_give_an_empty_grid() {
+        let (_, dims, data) = merge_blocks(&[], &mut mapping());
+
+        assert_eq!(dims, GridDims::ZERO);
+        assert!(data.is_empty());
+    }
+}
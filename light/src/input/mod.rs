@@ -1,25 +1,50 @@
 use std::collections::HashSet;
 
 use glam::{Vec2, vec2};
-use winit::event::{DeviceEvent, ElementState, KeyEvent, WindowEvent};
+use winit::event::{
+    DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+};
 use winit::keyboard::{KeyCode, PhysicalKey};
 
 pub struct Input {
     pressed_keys: HashSet<KeyCode>,
+    /// `pressed_keys` as of the last [`Input::end_frame`].
+    previous_keys: HashSet<KeyCode>,
+    pressed_buttons: HashSet<MouseButton>,
     mouse_delta: Vec2,
+    /// Lines scrolled, positive away from the user.
+    scroll_delta: f32,
 }
 
 impl Input {
     pub fn new() -> Self {
         Self {
             pressed_keys: HashSet::new(),
+            previous_keys: HashSet::new(),
+            pressed_buttons: HashSet::new(),
             mouse_delta: Vec2::ZERO,
+            scroll_delta: 0.0,
         }
     }
 
     pub fn submit_event(&mut self, event: &WindowEvent) {
         match event {
             WindowEvent::KeyboardInput { event, .. } => self.handle_key_event(event),
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    self.pressed_buttons.insert(*button);
+                }
+                ElementState::Released => {
+                    self.pressed_buttons.remove(button);
+                }
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    // roughly one line per 40 pixels, as on most platforms
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 40.0,
+                };
+            }
             _ => {}
         }
     }
@@ -37,6 +62,26 @@ impl Input {
         self.pressed_keys.contains(&keycode)
     }
 
+    /// Whether `keycode` went down since the last frame.
+    pub fn just_pressed(&self, keycode: KeyCode) -> bool {
+        self.pressed_keys.contains(&keycode) && !self.previous_keys.contains(&keycode)
+    }
+
+    /// Whether `keycode` went up since the last frame.
+    pub fn just_released(&self, keycode: KeyCode) -> bool {
+        !self.pressed_keys.contains(&keycode) && self.previous_keys.contains(&keycode)
+    }
+
+    /// Marks the end of a frame, so that later [`Input::just_pressed`] and
+    /// [`Input::just_released`] calls compare against the keys held now.
+    pub fn end_frame(&mut self) {
+        self.previous_keys.clone_from(&self.pressed_keys);
+    }
+
+    pub fn is_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
     pub fn mouse_delta(&self) -> Vec2 {
         self.mouse_delta
     }
@@ -45,6 +90,14 @@ impl Input {
         self.mouse_delta = Vec2::ZERO;
     }
 
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll_delta
+    }
+
+    pub fn reset_scroll_delta(&mut self) {
+        self.scroll_delta = 0.0;
+    }
+
     fn handle_key_event(&mut self, event: &KeyEvent) {
         let PhysicalKey::Code(keycode) = event.physical_key else {
             return;
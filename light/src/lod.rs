@@ -0,0 +1,93 @@
+use crate::grid::GridDims;
+
+/// Number of detail levels in a grid pyramid, including the full-resolution grid.
+pub const LOD_LEVELS: usize = 3;
+
+/// Appends copies of a grid at half and quarter resolution, so the renderer can march a
+/// coarser grid when the camera is far away. Levels are stored one after another, from
+/// finest to coarsest. `dims` must be divisible by 4 along each axis, which holds for any
+/// grid made of whole blocks.
+pub fn build_pyramid(grid: &[u32], dims: GridDims) -> Vec<u32> {
+    let mut pyramid = grid.to_vec();
+    let mut level = grid.to_vec();
+    let mut dims = dims;
+
+    for _ in 1..LOD_LEVELS {
+        level = downsample(&level, dims);
+        dims /= 2;
+
+        pyramid.extend_from_slice(&level);
+    }
+
+    pyramid
+}
+
+/// Halves the resolution of a grid. Each output cell takes the most common node among the
+/// 2×2×2 cells it covers, with ties going to non-air so thin features survive.
+pub fn downsample(grid: &[u32], dims: GridDims) -> Vec<u32> {
+    let (width, height) = (dims.x as usize, dims.y as usize);
+    let half = dims / 2;
+    let (half_width, half_height) = (half.x as usize, half.y as usize);
+    let mut result = vec![0; half.element_product() as usize];
+
+    for z in 0..half.z as usize {
+        for y in 0..half_height {
+            for x in 0..half_width {
+                let mut cells = [0; 8];
+
+                for (i, cell) in cells.iter_mut().enumerate() {
+                    let (dx, dy, dz) = (i & 1, (i >> 1) & 1, (i >> 2) & 1);
+                    let index = ((2 * z + dz) * height + (2 * y + dy)) * width + (2 * x + dx);
+                    *cell = grid[index];
+                }
+
+                result[(z * half_height + y) * half_width + x] = majority(&cells);
+            }
+        }
+    }
+
+    result
+}
+
+fn majority(cells: &[u32; 8]) -> u32 {
+    let id = |value: u32| value >> 16;
+    let count = |value: u32| cells.iter().filter(|&&c| id(c) == id(value)).count();
+
+    cells
+        .iter()
+        .copied()
+        .max_by_key(|&value| (count(value), id(value) != 0))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 1 << 16;
+
+    #[test]
+    fn downsample_breaks_ties_towards_non_air() {
+        let dims = GridDims::splat(2);
+
+        let tie = [0, STONE, 0, STONE, 0, STONE, 0, STONE];
+        assert_eq!(downsample(&tie, dims), [STONE]);
+
+        let mostly_air = [0, STONE, 0, 0, 0, STONE, 0, STONE];
+        assert_eq!(downsample(&mostly_air, dims), [0]);
+    }
+
+    #[test]
+    fn pyramid_holds_every_level() {
+        let dims = GridDims::new(32, 16, 8);
+        let grid = vec![STONE; dims.element_product() as usize];
+
+        let pyramid = build_pyramid(&grid, dims);
+
+        let volumes: u32 = (0..LOD_LEVELS)
+            .map(|level| (dims >> level as u32).element_product())
+            .sum();
+        assert_eq!(pyramid.len(), volumes as usize);
+        assert!(pyramid.iter().all(|&cell| cell == STONE));
+    }
+}
@@ -1,11 +1,17 @@
 #![allow(clippy::new_without_default)]
 #![allow(clippy::single_match)]
 
-use std::{error::Error, path::PathBuf};
-
-use glam::{Vec3, ivec3};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use asset::Mesh;
+use clap::Parser;
+use game::NodeDefs;
+use glam::{IVec3, Vec3, ivec3};
 use winit::dpi::PhysicalSize;
-use winit::event::{DeviceEvent, DeviceId};
+use winit::event::{DeviceEvent, DeviceId, ElementState, MouseButton};
 use winit::event_loop::ControlFlow;
 use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::{
@@ -14,39 +20,595 @@ use winit::{
     event_loop::{ActiveEventLoop, EventLoop},
     window::{Window, WindowId},
 };
-use world::{Block, Map, SqliteBackend, WorldMeta};
+use world::{Area, Block, EditHistory, Map, MapError, Node, WorldMeta};
 
 use crate::camera::Camera;
+use crate::cli::{
+    AdaptiveQualityArgs, Cli, Command, RenderLoop, TourArgs, ViewBlockArgs, ViewerArgs,
+    ViewerOptions,
+};
+use crate::grid::{GridDims, merge_blocks};
 use crate::input::Input;
-use crate::node::GlobalMapping;
-use crate::render::DataBuffer;
+use crate::node::{GlobalMapping, load_node_defs};
 use crate::render::Renderer;
+use crate::render::{DataBuffer, MeshBuffer};
+use crate::tour::Tour;
 
 pub mod camera;
+pub mod cli;
+pub mod commands;
+pub mod cpu_render;
+pub mod flood;
+pub mod grid;
 pub mod input;
+pub mod lod;
 pub mod node;
 pub mod render;
+pub mod tour;
+pub mod walk;
+
+/// Seconds between consecutive keyframes recorded with K.
+const TOUR_KEYFRAME_INTERVAL: f32 = 2.0;
+
+/// How much faster the camera moves while left Ctrl is held.
+const SPRINT_MULTIPLIER: f32 = 5.0;
+
+/// Factor the camera speed changes by per line scrolled.
+const SCROLL_SPEED_FACTOR: f32 = 1.2;
+
+/// Factor the orbit distance changes by per line scrolled while orbiting.
+const SCROLL_ZOOM_FACTOR: f32 = 1.1;
 
 struct App {
     renderer: Option<Renderer>,
     camera: Camera,
+    /// The camera as the viewer opened, to return to with Home.
+    initial_camera: Camera,
     input: Input,
-    map: Map,
+    block: Block,
+    block_pos: IVec3,
+    /// Blocks loaded around `block` to show it in context. They are drawn and can be
+    /// walked on, but not edited.
+    neighbors: Vec<(IVec3, Block)>,
+    node_defs: NodeDefs,
     global_mapping: GlobalMapping,
+    /// The loaded blocks as packed for the raymarcher, kept on the CPU for picking.
+    voxels: Vec<u32>,
+    voxels_dims: GridDims,
+    history: EditHistory,
     grid: Option<DataBuffer>,
+    mesh: Option<MeshBuffer>,
+    water_level: Option<i32>,
+    emissive_threshold: Option<u8>,
+    tour: Tour,
+    tour_started: Option<Instant>,
+    /// Where playing the tour saves its frames to, instead of showing them.
+    tour_out: Option<PathBuf>,
+    tour_fps: u32,
+    /// Keeps the camera at eye height above the ground instead of flying.
+    walking: bool,
+    /// Render scale while the camera moves, if adaptive quality is on.
+    moving_scale: Option<f32>,
+    settle_time: Duration,
+    last_moved: Option<Instant>,
+    render_loop: RenderLoop,
+    /// Samples per pixel the mesh is drawn with.
+    sample_count: u32,
+    y_clip: (Option<i32>, Option<i32>),
+    /// Set when the window shows something other than the current state, e.g. after a
+    /// resize.
+    needs_redraw: bool,
+    /// Render scale of the frame on screen.
+    drawn_scale: f32,
+    /// Why the viewer stopped, if rendering failed.
+    render_error: Option<Box<dyn Error>>,
+    /// Where to save the next frame drawn, after F2 was pressed.
+    screenshot_path: Option<PathBuf>,
 }
 
 impl App {
-    pub fn new(map: Map) -> Self {
+    /// Creates a viewer for `block`, which sits at `block_pos` in its world.
+    pub fn new(
+        block: Block,
+        block_pos: IVec3,
+        node_defs: NodeDefs,
+        water_level: Option<i32>,
+        emissive_threshold: Option<u8>,
+    ) -> Self {
+        let camera = Camera::new();
+
         Self {
             renderer: None,
-            camera: Camera::new(),
+            initial_camera: camera.clone(),
+            camera,
             input: Input::new(),
-            map,
+            block,
+            block_pos,
+            neighbors: Vec::new(),
+            node_defs,
             global_mapping: GlobalMapping::new(),
+            voxels: Vec::new(),
+            voxels_dims: GridDims::ZERO,
+            history: EditHistory::new(0),
             grid: None,
+            mesh: None,
+            water_level,
+            emissive_threshold,
+            tour: Tour::new(),
+            tour_started: None,
+            tour_out: None,
+            tour_fps: 30,
+            walking: false,
+            moving_scale: None,
+            settle_time: Duration::ZERO,
+            last_moved: None,
+            render_loop: RenderLoop::default(),
+            sample_count: 1,
+            y_clip: (None, None),
+            needs_redraw: true,
+            drawn_scale: 1.0,
+            render_error: None,
+            screenshot_path: None,
+        }
+    }
+
+    /// Applies the options shared by every way of opening the viewer.
+    fn set_options(&mut self, options: &ViewerOptions) {
+        self.set_adaptive_quality(&options.adaptive_quality);
+        self.set_render_loop(options.render_loop);
+        self.set_sample_count(options.msaa);
+        self.set_undo_depth(options.undo_depth);
+        self.set_y_clip(options.y_clip.y_min, options.y_clip.y_max);
+        self.set_tour_export(&options.tour);
+    }
+
+    fn set_render_loop(&mut self, render_loop: RenderLoop) {
+        self.render_loop = render_loop;
+    }
+
+    fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
+    }
+
+    /// Hides nodes outside the world Y range in the view.
+    fn set_y_clip(&mut self, min: Option<i32>, max: Option<i32>) {
+        self.y_clip = (min, max);
+    }
+
+    /// Shows `neighbors` around the block. The camera keeps its place relative to the
+    /// block, since the grid the camera moves in now starts at the lowest loaded block.
+    fn set_neighbors(&mut self, neighbors: Vec<(IVec3, Block)>) {
+        let old_offset = self.block_offset();
+        self.neighbors = neighbors;
+        let shift = (self.block_offset() - old_offset).as_vec3();
+
+        self.camera.position += shift;
+        self.initial_camera.position += shift;
+    }
+
+    /// Block position of the grid's lowest corner.
+    fn grid_origin(&self) -> IVec3 {
+        self.neighbors
+            .iter()
+            .map(|(pos, _)| *pos)
+            .fold(self.block_pos, IVec3::min)
+    }
+
+    /// Position of the edited block's lowest node in the grid.
+    fn block_offset(&self) -> IVec3 {
+        (self.block_pos - self.grid_origin()) * 16
+    }
+
+    /// Keeps the last `depth` node edits for undo.
+    fn set_undo_depth(&mut self, depth: usize) {
+        self.history = EditHistory::new(depth);
+    }
+
+    /// Lowers the resolution while the camera moves, and restores it once the camera has
+    /// been still for the configured time.
+    fn set_adaptive_quality(&mut self, args: &AdaptiveQualityArgs) {
+        self.moving_scale = args.moving_scale;
+        self.settle_time = Duration::from_secs_f32(args.settle_time.max(0.0));
+    }
+
+    fn render_scale(&self) -> f32 {
+        let moving = self
+            .last_moved
+            .is_some_and(|moved| moved.elapsed() < self.settle_time);
+
+        match self.moving_scale {
+            Some(scale) if moving => scale,
+            _ => 1.0,
+        }
+    }
+
+    fn add_tour_keyframe(&mut self) {
+        let time = if self.tour.is_empty() {
+            0.0
+        } else {
+            self.tour.duration() + TOUR_KEYFRAME_INTERVAL
+        };
+
+        self.tour.add_keyframe(time, self.camera.pose());
+        println!("tour keyframe {} at {time}s", self.tour.len());
+    }
+
+    fn set_tour_export(&mut self, args: &TourArgs) {
+        self.tour_out = args.tour_out.clone();
+        self.tour_fps = args.tour_fps;
+    }
+
+    /// Starts playing the tour from the beginning, or stops a tour that is playing. With
+    /// `--tour-out`, saves its frames instead.
+    fn toggle_tour(&mut self) {
+        if self.tour_started.is_some() {
+            self.tour_started = None;
+        } else if self.tour.len() >= 2
+            && let Some(dir) = self.tour_out.clone()
+        {
+            if let Err(err) = self.export_tour(&dir) {
+                eprintln!("failed to save the tour: {err}");
+            }
+        } else if self.tour.len() >= 2 {
+            self.camera.clear_orbit();
+            self.tour_started = Some(Instant::now());
+        }
+    }
+
+    /// Draws the tour off-screen at `tour_fps` and saves every frame in `dir` as a numbered
+    /// PNG, e.g. to be made into a video. Frames are as large as the window.
+    fn export_tour(&mut self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        let size = self
+            .renderer
+            .as_ref()
+            .and_then(Renderer::window)
+            .map_or(PhysicalSize::new(1280, 720), |window| window.inner_size());
+
+        let mut renderer = Renderer::new_headless(size.width, size.height)?;
+        if let Some(viewer) = &self.renderer {
+            renderer.set_backend(viewer.backend());
+        }
+        renderer.set_emissive_threshold(self.emissive_threshold);
+        renderer.set_y_clip(self.y_clip.0, self.y_clip.1);
+        renderer.set_sample_count(self.sample_count);
+
+        let (origin, dims, grid, mesh) = self.prepare_scene(&mut renderer);
+        let pyramid = lod::build_pyramid(&grid, dims);
+        let data = renderer.create_data_buffer(origin, dims, bytemuck::cast_slice(&pyramid));
+        let mesh = renderer.create_mesh_buffer(&mesh);
+
+        std::fs::create_dir_all(dir)?;
+
+        let mut camera = self.camera.clone();
+        camera.clear_orbit();
+        let frame_count = (self.tour.duration() * self.tour_fps as f32).ceil() as u32 + 1;
+
+        for frame in 0..frame_count {
+            let Some(pose) = self.tour.sample(frame as f32 / self.tour_fps as f32) else {
+                break;
+            };
+            camera.set_pose(&pose);
+
+            renderer.render(&camera, &data, &mesh)?;
+            let path = dir.join(format!("frame-{frame:05}.png"));
+            renderer.capture_frame().save(path)?;
+        }
+
+        renderer.shutdown();
+        println!("saved {frame_count} tour frames to {}", dir.display());
+
+        Ok(())
+    }
+
+    /// Moves the camera along the tour. Returns `false` once the tour has finished.
+    fn play_tour(&mut self, started: Instant) -> bool {
+        let t = started.elapsed().as_secs_f32();
+
+        if let Some(pose) = self.tour.sample(t) {
+            self.camera.set_pose(&pose);
+        }
+
+        t < self.tour.duration()
+    }
+
+    /// Replaces the node in the middle of the view with air. Edits stay in memory and are
+    /// not written back to the world. Nodes of the neighboring blocks are left alone.
+    fn remove_picked_node(&mut self) {
+        let node_flags = self.global_mapping.node_flags(&self.node_defs);
+        let Some(pos) = cpu_render::pick(&self.camera, &self.voxels, self.voxels_dims, &node_flags)
+        else {
+            return;
+        };
+
+        let pos = pos - self.block_offset();
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(IVec3::splat(16)).any() {
+            return;
+        }
+
+        let air = Node {
+            id: self.block.get_or_insert_id("air"),
+            param1: 0,
+            param2: 0,
+        };
+        self.history.set_node(&mut self.block, pos, air);
+        self.upload_block();
+    }
+
+    /// Prints the name of the node in the middle of the view, as the raymarcher drew it.
+    fn print_picked_node(&self) {
+        let Some(renderer) = &self.renderer else {
+            return;
+        };
+        let Some(window) = renderer.window() else {
+            return;
+        };
+
+        let size = window.inner_size();
+        match renderer.read_node_id_at(size.width / 2, size.height / 2) {
+            Some(id) => println!("{}", self.global_mapping.name(id).unwrap_or("unknown")),
+            None => println!("no node in the middle of the view"),
+        }
+    }
+
+    fn undo(&mut self) {
+        if self.history.undo(&mut self.block).is_some() {
+            self.upload_block();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.history.redo(&mut self.block).is_some() {
+            self.upload_block();
+        }
+    }
+
+    /// Saves the next frame next to the executable, named after the current time so that
+    /// earlier screenshots are kept.
+    fn take_screenshot(&mut self) {
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(PathBuf::from))
+            .unwrap_or_default();
+        let path = dir.join(format!("screenshot-{millis}.png"));
+
+        match renderer.request_capture() {
+            Ok(()) => {
+                self.screenshot_path = Some(path);
+                self.needs_redraw = true;
+            }
+            Err(err) => eprintln!("failed to take screenshot: {err}"),
+        }
+    }
+
+    /// Sends the loaded blocks to the renderer again, e.g. after an edit.
+    fn upload_block(&mut self) {
+        let Some(mut renderer) = self.renderer.take() else {
+            return;
+        };
+
+        let (origin, dims, grid, mesh) = self.prepare_scene(&mut renderer);
+
+        let pyramid = lod::build_pyramid(&grid, dims);
+        let data = bytemuck::cast_slice(&pyramid);
+        match &mut self.grid {
+            Some(buffer) => renderer.update_data_buffer(buffer, origin, dims, data),
+            None => self.grid = Some(renderer.create_data_buffer(origin, dims, data)),
+        }
+        let mesh = renderer.create_mesh_buffer(&mesh);
+
+        self.renderer = Some(renderer);
+        self.voxels = grid;
+        self.voxels_dims = dims;
+        self.mesh = Some(mesh);
+        self.needs_redraw = true;
+    }
+
+    /// Merges the loaded blocks into one grid and mesh, and gives `renderer` the node flags
+    /// and flooding that go with them. Returns the grid's origin block, size and nodes, and
+    /// the mesh.
+    fn prepare_scene(&mut self, renderer: &mut Renderer) -> (IVec3, GridDims, Vec<u32>, Mesh) {
+        let blocks: Vec<_> = std::iter::once((self.block_pos, &self.block))
+            .chain(self.neighbors.iter().map(|(pos, block)| (*pos, block)))
+            .collect();
+        let (origin, dims, grid) = merge_blocks(&blocks, &mut self.global_mapping);
+        renderer.set_node_flags(&self.global_mapping.node_flags(&self.node_defs));
+
+        if let Some(water_level) = self.water_level {
+            let level = water_level - origin.y * 16;
+            renderer.set_flood_level(Some(level), &flood::flood_fill(&grid, dims, level));
+        }
+
+        let mut mesh = Mesh::new();
+        for (pos, block) in &blocks {
+            let offset = ((pos - origin) * 16).as_vec3();
+            mesh.append(
+                &::render::meshing::make_mesh(block, &self.node_defs),
+                offset,
+            );
+        }
+
+        (origin, dims, grid, mesh)
+    }
+
+    /// Stops the viewer, shutting the renderer down while the window is still open.
+    fn exit(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(renderer) = self.renderer.take() {
+            renderer.shutdown();
+        }
+
+        event_loop.exit();
+    }
+
+    fn is_ctrl_pressed(&self) -> bool {
+        self.input.is_key_pressed(KeyCode::ControlLeft)
+            || self.input.is_key_pressed(KeyCode::ControlRight)
+    }
+
+    /// Puts the camera back where the viewer started, stopping any tour or orbit.
+    fn reset_camera(&mut self) {
+        self.tour_started = None;
+        // settings stay as they are
+        let mut camera = self.initial_camera.clone();
+        camera.set_speed(self.camera.speed());
+        let (sensitivity_x, sensitivity_y) = self.camera.sensitivity();
+        camera.set_sensitivity(sensitivity_x, sensitivity_y);
+        camera.set_invert_y(self.camera.invert_y());
+        self.camera = camera;
+        self.needs_redraw = true;
+    }
+
+    /// Orbits the node in the middle of the view, or the center of the loaded blocks if
+    /// there is none, or returns to free flight.
+    fn toggle_orbit(&mut self) {
+        if self.camera.orbit().is_some() {
+            self.camera.clear_orbit();
+        } else {
+            let node_flags = self.global_mapping.node_flags(&self.node_defs);
+            let pivot =
+                match cpu_render::pick(&self.camera, &self.voxels, self.voxels_dims, &node_flags) {
+                    Some(pos) => pos.as_vec3() + 0.5,
+                    None => self.voxels_dims.as_vec3() / 2.0,
+                };
+            let distance = self.camera.position.distance(pivot);
+            self.camera.set_orbit(pivot, distance);
         }
     }
+
+    /// Switches between flying and walking on the loaded blocks.
+    fn toggle_walking(&mut self) {
+        self.walking = !self.walking;
+
+        if self.walking {
+            self.camera.clear_orbit();
+            self.camera.position =
+                walk::snap_to_ground(self.camera.position, |pos| self.is_walkable(pos));
+        }
+    }
+
+    /// Whether the node at grid position `pos` can be stood on. Everything outside the
+    /// loaded blocks is empty.
+    fn is_walkable(&self, pos: IVec3) -> bool {
+        let block_pos = self.grid_origin() + pos.div_euclid(IVec3::splat(16));
+
+        let block = if block_pos == self.block_pos {
+            &self.block
+        } else {
+            match self.neighbors.iter().find(|(pos, _)| *pos == block_pos) {
+                Some((_, block)) => block,
+                None => return false,
+            }
+        };
+
+        let node = block.get_node(pos.rem_euclid(IVec3::splat(16)));
+        block
+            .get_name_by_id(node.id)
+            .is_some_and(|name| self.node_defs.is_walkable(name))
+    }
+
+    /// The camera's speed, or a multiple of it while sprinting with left Ctrl.
+    fn movement_speed(&self) -> f32 {
+        if self.input.is_key_pressed(KeyCode::ControlLeft) {
+            self.camera.speed() * SPRINT_MULTIPLIER
+        } else {
+            self.camera.speed()
+        }
+    }
+
+    /// Like flying, but W, A, S and D move along the ground and the camera can't pass
+    /// through walls.
+    fn walk_camera(&mut self) {
+        let (forward, right) = self.camera.forward_right();
+        let forward = Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
+        let right = Vec3::new(right.x, 0.0, right.z).normalize_or_zero();
+        let speed = self.movement_speed();
+
+        let mut movement_delta = Vec3::ZERO;
+
+        if self.input.is_key_pressed(KeyCode::KeyW) {
+            movement_delta += forward;
+        }
+
+        if self.input.is_key_pressed(KeyCode::KeyS) {
+            movement_delta -= forward;
+        }
+
+        if self.input.is_key_pressed(KeyCode::KeyA) {
+            movement_delta -= right;
+        }
+
+        if self.input.is_key_pressed(KeyCode::KeyD) {
+            movement_delta += right;
+        }
+
+        let delta = movement_delta.normalize_or_zero() * speed;
+        self.camera.position = walk::walk(self.camera.position, delta, |pos| self.is_walkable(pos));
+
+        self.camera.turn(self.input.mouse_delta());
+        self.input.reset_mouse_delta();
+    }
+
+    fn fly_camera(&mut self) {
+        let (forward, right) = self.camera.forward_right();
+        let speed = self.movement_speed();
+
+        let mut movement_delta = Vec3::ZERO;
+
+        if self.input.is_key_pressed(KeyCode::KeyW) {
+            movement_delta += forward;
+        }
+
+        if self.input.is_key_pressed(KeyCode::KeyS) {
+            movement_delta -= forward;
+        }
+
+        if self.input.is_key_pressed(KeyCode::KeyA) {
+            movement_delta -= right;
+        }
+
+        if self.input.is_key_pressed(KeyCode::KeyD) {
+            movement_delta += right;
+        }
+
+        if self.input.is_key_pressed(KeyCode::Space) {
+            movement_delta += Vec3::Y;
+        }
+
+        if self.input.is_key_pressed(KeyCode::ShiftLeft) {
+            movement_delta -= Vec3::Y;
+        }
+
+        self.camera.position += movement_delta.normalize_or_zero() * speed;
+
+        self.camera.turn(self.input.mouse_delta());
+        self.input.reset_mouse_delta();
+    }
+
+    /// W and S, or scrolling, move towards and away from the pivot; dragging with the left
+    /// button turns around it.
+    fn orbit_camera(&mut self) {
+        let speed = self.movement_speed();
+
+        if self.input.is_key_pressed(KeyCode::KeyW) {
+            self.camera.zoom_orbit(-speed);
+        }
+
+        if self.input.is_key_pressed(KeyCode::KeyS) {
+            self.camera.zoom_orbit(speed);
+        }
+
+        if self.input.is_mouse_button_pressed(MouseButton::Left) {
+            self.camera.turn(self.input.mouse_delta());
+        }
+        self.input.reset_mouse_delta();
+    }
 }
 
 impl ApplicationHandler for App {
@@ -56,23 +618,32 @@ impl ApplicationHandler for App {
             .with_inner_size(PhysicalSize::new(1280, 720));
 
         let window = event_loop.create_window(window_attributes).unwrap();
-        let renderer = Renderer::new(window);
+        let mut renderer = match Renderer::new(window) {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                eprintln!("cannot show the viewer: {err}");
+                self.render_error = Some(err.into());
+                event_loop.exit();
+                return;
+            }
+        };
+        renderer.set_emissive_threshold(self.emissive_threshold);
+        renderer.set_y_clip(self.y_clip.0, self.y_clip.1);
+        renderer.set_sample_count(self.sample_count);
 
         let adapter_info = renderer.adapter_info();
-        renderer.window().set_title(&format!(
-            "Light ({} on {})",
-            adapter_info.backend, adapter_info.name
-        ));
+        if let Some(window) = renderer.window() {
+            window.set_title(&format!(
+                "Light ({} on {})",
+                adapter_info.backend, adapter_info.name
+            ));
+        }
 
         let air_id = self.global_mapping.get_or_insert_id("air");
         assert_eq!(air_id, 0);
 
-        let block = self.map.get_block(ivec3(0, 2, 0)).unwrap();
-        let grid = block_to_grid(&block, &mut self.global_mapping);
-        let grid = renderer.create_data_buffer(bytemuck::cast_slice(&grid));
-
         self.renderer = Some(renderer);
-        self.grid = Some(grid);
+        self.upload_block();
     }
 
     fn window_event(
@@ -81,18 +652,66 @@ impl ApplicationHandler for App {
         _window_id: WindowId,
         event: WindowEvent,
     ) {
-        event_loop.set_control_flow(ControlFlow::Poll);
-
         match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::CloseRequested => self.exit(event_loop),
+            WindowEvent::RedrawRequested => self.needs_redraw = true,
             WindowEvent::Resized(size) => {
                 if let Some(renderer) = &mut self.renderer {
                     renderer.resize(size);
                 }
+                self.needs_redraw = true;
             }
             WindowEvent::KeyboardInput { ref event, .. } => {
                 if let PhysicalKey::Code(KeyCode::Escape) = event.physical_key {
-                    event_loop.exit();
+                    self.exit(event_loop);
+                }
+
+                if event.physical_key == PhysicalKey::Code(KeyCode::Tab)
+                    && event.state == ElementState::Pressed
+                    && !event.repeat
+                    && let Some(renderer) = &mut self.renderer
+                {
+                    renderer.set_backend(renderer.backend().toggled());
+                    self.needs_redraw = true;
+                }
+
+                if event.physical_key == PhysicalKey::Code(KeyCode::KeyO)
+                    && event.state == ElementState::Pressed
+                    && !event.repeat
+                {
+                    self.toggle_orbit();
+                }
+
+                if event.state == ElementState::Pressed && !event.repeat {
+                    match event.physical_key {
+                        PhysicalKey::Code(KeyCode::KeyK) => self.add_tour_keyframe(),
+                        PhysicalKey::Code(KeyCode::KeyP) => self.toggle_tour(),
+                        PhysicalKey::Code(KeyCode::Home) => self.reset_camera(),
+                        PhysicalKey::Code(KeyCode::KeyG) => self.toggle_walking(),
+                        PhysicalKey::Code(KeyCode::Delete) => self.remove_picked_node(),
+                        PhysicalKey::Code(KeyCode::KeyZ) if self.is_ctrl_pressed() => self.undo(),
+                        PhysicalKey::Code(KeyCode::KeyY) if self.is_ctrl_pressed() => self.redo(),
+                        PhysicalKey::Code(KeyCode::F4) => {
+                            if let Some(renderer) = &mut self.renderer {
+                                renderer.set_show_mapchunk_bounds(!renderer.show_mapchunk_bounds());
+                                self.needs_redraw = true;
+                            }
+                        }
+                        PhysicalKey::Code(KeyCode::KeyV) => {
+                            if let Some(renderer) = &mut self.renderer {
+                                renderer.set_vsync(!renderer.vsync());
+                                println!("vsync {}", if renderer.vsync() { "on" } else { "off" });
+                            }
+                        }
+                        PhysicalKey::Code(KeyCode::F2) => self.take_screenshot(),
+                        PhysicalKey::Code(KeyCode::KeyI) => self.print_picked_node(),
+                        PhysicalKey::Code(KeyCode::F3) => {
+                            if let (Some(renderer), Some(grid)) = (&self.renderer, &self.grid) {
+                                renderer.dump_uniforms(&self.camera, grid);
+                            }
+                        }
+                        _ => {}
+                    }
                 }
             }
             _ => {}
@@ -111,110 +730,217 @@ impl ApplicationHandler for App {
     }
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-        let Some(renderer) = &mut self.renderer else {
-            return;
-        };
-
-        let Some(grid) = &self.grid else {
-            return;
-        };
-
-        let (forward, right) = self.camera.forward_right();
-        let speed = 0.1;
-
-        let mut movement_delta = Vec3::ZERO;
-
-        if self.input.is_key_pressed(KeyCode::KeyW) {
-            movement_delta += forward;
+        let pose = self.camera.pose();
+
+        // scrolling zooms while orbiting, and changes the speed otherwise
+        let scroll = self.input.scroll_delta();
+        match self.camera.orbit() {
+            Some(orbit) => {
+                let distance = orbit.distance * SCROLL_ZOOM_FACTOR.powf(-scroll);
+                self.camera.zoom_orbit(distance - orbit.distance);
+            }
+            None => self
+                .camera
+                .set_speed(self.camera.speed() * SCROLL_SPEED_FACTOR.powf(scroll)),
         }
+        self.input.reset_scroll_delta();
 
-        if self.input.is_key_pressed(KeyCode::KeyS) {
-            movement_delta -= forward;
+        if let Some(started) = self.tour_started {
+            if !self.play_tour(started) {
+                self.tour_started = None;
+            }
+            self.input.reset_mouse_delta();
+        } else if self.camera.orbit().is_some() {
+            self.orbit_camera();
+        } else if self.walking {
+            self.walk_camera();
+        } else {
+            self.fly_camera();
         }
+        self.input.end_frame();
 
-        if self.input.is_key_pressed(KeyCode::KeyA) {
-            movement_delta -= right;
+        let moved = self.camera.pose() != pose;
+        if moved {
+            self.last_moved = Some(Instant::now());
         }
+        let render_scale = self.render_scale();
+
+        // Keep polling while the camera moves so held keys keep flying it, and until the
+        // frame is back at full resolution; otherwise sleep until the next event.
+        let busy = moved || self.tour_started.is_some() || render_scale < 1.0;
+        let control_flow = match self.render_loop {
+            RenderLoop::Continuous => ControlFlow::Poll,
+            RenderLoop::OnDemand if busy => ControlFlow::Poll,
+            RenderLoop::OnDemand => ControlFlow::Wait,
+        };
+        event_loop.set_control_flow(control_flow);
 
-        if self.input.is_key_pressed(KeyCode::KeyD) {
-            movement_delta += right;
+        let redraw = self.render_loop == RenderLoop::Continuous
+            || self.needs_redraw
+            || moved
+            || render_scale != self.drawn_scale;
+        if !redraw {
+            return;
         }
 
-        if self.input.is_key_pressed(KeyCode::Space) {
-            movement_delta += Vec3::Y;
-        }
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
 
-        if self.input.is_key_pressed(KeyCode::ShiftLeft) {
-            movement_delta -= Vec3::Y;
-        }
+        let (Some(grid), Some(mesh)) = (&self.grid, &self.mesh) else {
+            return;
+        };
 
-        self.camera.position += movement_delta.normalize_or_zero() * speed;
+        renderer.set_render_scale(render_scale);
+        if let Err(err) = renderer.render(&self.camera, grid, mesh) {
+            self.render_error = Some(err.into());
+            event_loop.exit();
+            return;
+        }
 
-        let sensitivity = 0.1;
-        let mouse_delta = self.input.mouse_delta() * sensitivity;
-        self.camera.rotate(mouse_delta.y, mouse_delta.x);
-        self.input.reset_mouse_delta();
+        self.needs_redraw = false;
+        self.drawn_scale = render_scale;
 
-        renderer.render(&self.camera, grid);
+        // frames that couldn't be acquired are drawn again, so the capture waits until then
+        if let Some(image) = renderer.take_captured_frame()
+            && let Some(path) = self.screenshot_path.take()
+        {
+            match image.save(&path) {
+                Ok(()) => println!("saved screenshot to {}", path.display()),
+                Err(err) => eprintln!("failed to take screenshot: {err}"),
+            }
+        }
     }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let Some(world_path) = std::env::args().nth(1) else {
-        eprintln!("world path required");
-        std::process::exit(1);
-    };
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Stats(args)) => commands::stats::run(args),
+        Some(Command::Diff(args)) => commands::diff::run(args),
+        Some(Command::Vox(args)) => commands::vox::run(args),
+        Some(Command::Gltf(args)) => commands::gltf::run(args),
+        Some(Command::Render(args)) => commands::render::run(args),
+        Some(Command::Sample(args)) => commands::sample::run(args),
+        Some(Command::ViewBlock(args)) => run_block_viewer(args),
+        Some(Command::Find(args)) => commands::find::run(args),
+        Some(Command::Check(args)) => commands::check::run(args),
+        Some(Command::Recompress(args)) => commands::recompress::run(args),
+        None => run_viewer(cli.viewer),
+    }
+}
 
-    let world_path = PathBuf::from(world_path);
+fn run_viewer(args: ViewerArgs) -> Result<(), Box<dyn Error>> {
+    let world_path = args.world.unwrap();
     let world_meta_path = world_path.join("world.mt");
 
     let world_meta = WorldMeta::open(world_meta_path)?;
+    let map = world::open_map(&world_path, &world_meta)?;
 
-    let backend = world_meta.get_str("backend").unwrap();
+    println!("{}", map.summary()?);
 
-    let map = match backend {
-        "sqlite3" => {
-            let sqlite_path = world_path.join("map.sqlite");
-            let sqlite = SqliteBackend::new(sqlite_path)?;
-            Map::new(sqlite)
-        }
-        "postgres" => {
-            unimplemented!()
-        }
-        _ => {
-            eprintln!("unknown backend: {backend}");
-            std::process::exit(1);
-        }
-    };
+    let (block_pos, block) = initial_block(&map)?;
+    let neighbors = neighbor_blocks(&map, block_pos, args.blocks)?;
 
-    let event_loop = EventLoop::new()?;
-    let mut app = App::new(map);
+    let mut app = App::new(
+        block,
+        block_pos,
+        load_node_defs(args.options.game.as_deref())?,
+        args.water_level,
+        args.options.emissive_threshold,
+    );
+    app.set_neighbors(neighbors);
+    app.set_options(&args.options);
 
-    event_loop.run_app(&mut app)?;
+    run_app(app)
+}
 
-    Ok(())
+/// Loads the other blocks of a cube `size` blocks wide around `block_pos`. Blocks that are
+/// missing or fail to parse are left out.
+fn neighbor_blocks(
+    map: &Map,
+    block_pos: IVec3,
+    size: u32,
+) -> Result<Vec<(IVec3, Block)>, Box<dyn Error>> {
+    let min = block_pos - IVec3::splat((size as i32 - 1) / 2);
+    let area = Area::new(min, min + IVec3::splat(size as i32 - 1));
+    let positions: Vec<_> = area.iter().filter(|&pos| pos != block_pos).collect();
+
+    let mut neighbors = Vec::new();
+    for (pos, result) in map.get_blocks(&positions)? {
+        match result {
+            Ok(block) => neighbors.push((pos, Arc::unwrap_or_clone(block))),
+            Err(err) => eprintln!("block {pos}: skipped, {err}"),
+        }
+    }
+
+    Ok(neighbors)
 }
 
-fn block_to_grid(block: &Block, global_mapping: &mut GlobalMapping) -> Vec<u32> {
-    let mut data = vec![0; 16 * 16 * 16];
+/// Loads the block the viewer starts at: (0, 2, 0) if the world has it, or else the
+/// non-empty block nearest the middle of the stored ones.
+fn initial_block(map: &Map) -> Result<(IVec3, Block), Box<dyn Error>> {
+    let default_pos = ivec3(0, 2, 0);
+    match map.get_block(default_pos) {
+        Ok(block) => return Ok((default_pos, Arc::unwrap_or_clone(block))),
+        Err(MapError::BlockNotFound) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let mut positions = map.block_positions()?;
+    if positions.is_empty() {
+        return Err("the world has no blocks".into());
+    }
 
-    for z in 0..16 {
-        for y in 0..16 {
-            for x in 0..16 {
-                let node = block.get_node(ivec3(x, y, z));
-                let name = block.get_name_by_id(node.id).unwrap();
-                let global_id = global_mapping.get_or_insert_id(name);
+    let center = positions.iter().map(|pos| pos.as_vec3()).sum::<Vec3>() / positions.len() as f32;
+    positions.sort_by(|a, b| {
+        let a = a.as_vec3().distance_squared(center);
+        let b = b.as_vec3().distance_squared(center);
+        a.total_cmp(&b)
+    });
 
-                let mut value = 0;
-                value |= (global_id as u32) << 16;
-                value |= node.param1 as u32;
-                value |= node.param2 as u32;
+    for pos in positions {
+        let Ok(block) = map.get_block(pos) else {
+            continue;
+        };
 
-                let index = (z * 16 * 16 + y * 16 + x) as usize;
-                data[index] = value;
-            }
+        let is_empty = block
+            .node_counts()
+            .keys()
+            .all(|&name| name == "air" || name == "ignore");
+        if !is_empty {
+            println!("block {default_pos} not found, starting at block {pos} instead");
+            return Ok((pos, Arc::unwrap_or_clone(block)));
         }
     }
 
-    data
+    Err("the world has only empty blocks".into())
+}
+
+/// Views a single block read from a raw blob, as stored in the map database.
+fn run_block_viewer(args: ViewBlockArgs) -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(&args.file)?;
+    let block = Block::parse_data(&data)?;
+
+    let mut app = App::new(
+        block,
+        IVec3::ZERO,
+        load_node_defs(args.options.game.as_deref())?,
+        None,
+        args.options.emissive_threshold,
+    );
+    app.set_options(&args.options);
+
+    run_app(app)
+}
+
+fn run_app(mut app: App) -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::new()?;
+    event_loop.run_app(&mut app)?;
+
+    match app.render_error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
 }
@@ -1,31 +1,196 @@
 use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
 
+use game::{NodeDefs, NodeDefsError};
+
+/// Set in a node's flags when the raymarcher should see through it, e.g. glass or plants.
+pub const NODE_SEE_THROUGH: u32 = 1 << 0;
+/// Set in a node's flags when it isn't drawn at all, like air.
+pub const NODE_HIDDEN: u32 = 1 << 1;
 
 pub struct GlobalMapping {
-    mapping: HashMap<String, u16>,
-    last_id: u16,
+    mapping: HashMap<Arc<str>, u16>,
+    /// Node names indexed by id, as ids are handed out in order. Interned, so they share
+    /// storage with the blocks' own mappings.
+    names: Vec<Arc<str>>,
 }
 
 impl GlobalMapping {
     pub fn new() -> Self {
         Self {
             mapping: HashMap::new(),
-            last_id: 0,
+            names: Vec::new(),
         }
     }
 
+    /// Returns the global id of `name`, giving it the next free one if it has none yet.
+    ///
+    /// Panics once all 65536 ids are taken, since the grid packs them into 16 bits.
     pub fn get_or_insert_id(&mut self, name: &str) -> u16 {
         if let Some(id) = self.mapping.get(name).cloned() {
             return id;
         }
 
-        let id = self.last_id;
-
-        self.mapping.insert(name.to_string(), id);
-        println!("{id} = {name}");
+        let id = u16::try_from(self.names.len()).expect("at most 65536 node names have an id");
+        let name = world::intern(name);
 
-        self.last_id += 1;
+        self.mapping.insert(name.clone(), id);
+        self.names.push(name);
 
         id
     }
+
+    /// The name of the node with global `id`, if one has been given that id.
+    pub fn name(&self, id: u16) -> Option<&str> {
+        self.names.get(id as usize).map(|name| name.as_ref())
+    }
+
+    /// How many distinct nodes have been given an id.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the render flags of every node in the mapping, indexed by global id.
+    pub fn node_flags(&self, node_defs: &NodeDefs) -> Vec<u32> {
+        let mut flags = vec![0; self.names.len()];
+
+        for (name, &id) in &self.mapping {
+            if !node_defs.is_visible(name) {
+                flags[id as usize] |= NODE_HIDDEN;
+            } else if !node_defs.is_face_solid(name) {
+                flags[id as usize] |= NODE_SEE_THROUGH;
+            }
+        }
+
+        flags
+    }
+}
+
+/// Reads the node definitions of the game at `game_path`, or returns empty definitions
+/// that treat every node as a full cube.
+pub fn load_node_defs(game_path: Option<&Path>) -> Result<NodeDefs, NodeDefsError> {
+    match game_path {
+        Some(path) => NodeDefs::scan(path),
+        None => Ok(NodeDefs::new()),
+    }
+}
+
+/// How nodes without textures are given colors.
+pub enum ColorScheme {
+    /// Hash the full node name. Every node gets its own color, but related nodes can end up
+    /// looking nothing alike, or two unrelated ones nearly the same.
+    NameHash,
+    /// Hash the mod prefix (`default` in `default:stone`) for the hue, and the full name for
+    /// a small change in brightness, so nodes from the same mod look alike.
+    CategoryHash,
+    /// Colors from a minetestmapper-style `colors.txt`. Nodes missing from it fall back to
+    /// [`ColorScheme::NameHash`].
+    Palette(HashMap<String, [u8; 4]>),
+}
+
+impl ColorScheme {
+    /// Reads a `colors.txt` with one `name r g b [a]` entry per line and `#` comments.
+    pub fn load_palette(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let mut colors = HashMap::new();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid_line = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid color on line {}: `{line}`", line_number + 1),
+                )
+            };
+
+            let mut parts = line.split_whitespace();
+            let name = parts.next().ok_or_else(invalid_line)?;
+            let components = parts
+                .map(|part| part.parse::<u8>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid_line())?;
+
+            let color = match components[..] {
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, a, ..] => [r, g, b, a],
+                _ => return Err(invalid_line()),
+            };
+
+            colors.insert(name.to_owned(), color);
+        }
+
+        Ok(ColorScheme::Palette(colors))
+    }
+
+    pub fn color(&self, name: &str) -> [u8; 4] {
+        match self {
+            ColorScheme::NameHash => {
+                let [r, g, b, ..] = hash(name).to_le_bytes();
+                [r, g, b, 255]
+            }
+            ColorScheme::CategoryHash => {
+                let (category, _) = name.split_once(':').unwrap_or(("", name));
+                let [r, g, b, ..] = hash(category).to_le_bytes();
+                let brightness = 0.75 + 0.25 * (hash(name) % 256) as f32 / 255.0;
+                let scale = |c: u8| (c as f32 * brightness) as u8;
+
+                [scale(r), scale(g), scale(b), 255]
+            }
+            ColorScheme::Palette(colors) => colors
+                .get(name)
+                .copied()
+                .unwrap_or_else(|| ColorScheme::NameHash.color(name)),
+        }
+    }
+}
+
+/// Colors nodes that have no texture information, for exporters that take a palette.
+pub fn default_palette(scheme: &ColorScheme) -> impl Fn(&str) -> [u8; 4] + '_ {
+    |name| scheme.color(name)
+}
+
+/// 64-bit FNV-1a. Colors come from this, so it must stay the same across builds and
+/// platforms, unlike the standard library's hashers.
+fn hash(value: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    value.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_is_fnv1a() {
+        assert_eq!(hash(""), 0xcbf29ce484222325);
+        assert_eq!(hash("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash("foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn global_mapping_looks_up_names() {
+        let mut mapping = GlobalMapping::new();
+        let air = mapping.get_or_insert_id("air");
+        let stone = mapping.get_or_insert_id("default:stone");
+
+        assert_eq!(mapping.get_or_insert_id("air"), air);
+        assert_eq!(mapping.name(air), Some("air"));
+        assert_eq!(mapping.name(stone), Some("default:stone"));
+        assert_eq!(mapping.name(2), None);
+        assert_eq!(mapping.len(), 2);
+    }
 }
@@ -0,0 +1,30 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) normal: vec3f,
+    @location(2) texcoord: vec2f,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) uv: vec2f,
+};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4(model.position, 1.0);
+    // texcoords run from 0 to 2 across the screen, bottom to top, while texture rows
+    // run top to bottom
+    out.uv = vec2(model.texcoord.x, 2.0 - model.texcoord.y) * 0.5;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    return textureSample(source, source_sampler, in.uv);
+}
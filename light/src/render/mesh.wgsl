@@ -0,0 +1,44 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) normal: vec3f,
+    @location(2) texcoord: vec2f,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) normal: vec3f,
+    @location(1) grid_y: f32,
+};
+
+struct Uniforms {
+    view_projection: mat4x4f,
+    y_clip_min: i32,
+    y_clip_max: i32,
+};
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = uniforms.view_projection * vec4(model.position, 1.0);
+    out.normal = model.normal;
+    // cubes are centered on node positions, half a node off the grid
+    out.grid_y = model.position.y + 0.5;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    // step back into the node a face belongs to, so top faces count as the node below
+    let node_y = i32(floor(in.grid_y - in.normal.y * 0.01));
+    if node_y < uniforms.y_clip_min || node_y > uniforms.y_clip_max {
+        discard;
+    }
+
+    let sun_dir = normalize(vec3(0.5, 0.7, 1.0));
+    let light = saturate(max(dot(in.normal, sun_dir), 0.2));
+    return vec4(light, 0.0, 0.0, 1.0);
+}
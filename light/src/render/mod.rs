@@ -1,21 +1,38 @@
-use glam::{Vec3, vec2, vec3};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use glam::{IVec3, Mat4, UVec3, Vec3, vec2, vec3};
+use image::RgbaImage;
 use pollster::FutureExt;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
-    Adapter, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    Adapter, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
     BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType,
-    BufferDescriptor, BufferUsages, Color, Device, DeviceDescriptor, FragmentState, Instance,
-    InstanceDescriptor, LoadOp, Operations, PipelineLayoutDescriptor, PowerPreference,
-    PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDescriptor,
-    RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor,
-    ShaderSource, ShaderStages, StoreOp, Surface, SurfaceConfiguration, SurfaceTargetUnsafe,
+    BufferDescriptor, BufferUsages, Color, CompareFunction, DepthStencilState, Device,
+    DeviceDescriptor, FragmentState, Instance, InstanceDescriptor, LoadOp, Operations,
+    PipelineLayoutDescriptor, PowerPreference, PresentMode, PrimitiveState, PrimitiveTopology,
+    Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, StoreOp, Surface, SurfaceConfiguration, SurfaceError, SurfaceTargetUnsafe,
     VertexAttribute, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
 };
-use wgpu::{AdapterInfo, CommandEncoderDescriptor, TextureViewDescriptor};
+use wgpu::{
+    AdapterInfo, COPY_BYTES_PER_ROW_ALIGNMENT, CommandEncoder, CommandEncoderDescriptor,
+    CompositeAlphaMode, CreateSurfaceError, ErrorFilter, Extent3d, Features, FilterMode,
+    IndexFormat, MapMode, Origin3d, PipelineLayout, PollType, QuerySet, QuerySetDescriptor,
+    QueryType, RenderPass, RenderPassDepthStencilAttachment, RenderPassTimestampWrites,
+    RequestDeviceError, Sampler, SamplerBindingType, SamplerDescriptor, ShaderModule,
+    TexelCopyBufferInfo, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension,
+};
 use winit::{dpi::PhysicalSize, window::Window};
 
-use asset::{Mesh, Vertex};
 use crate::camera::Camera;
+use crate::grid::GridDims;
+use crate::lod::LOD_LEVELS;
+use asset::{Mesh, Vertex};
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -24,11 +41,50 @@ struct ShaderUniforms {
     fov: f32,
     position: Vec3,
     aspect_ratio: f32,
+    flood_level: f32,
+    flood_enabled: u32,
+    lod: u32,
+    emissive_threshold: u32,
+    y_clip_min: i32,
+    y_clip_max: i32,
+    show_mapchunk_bounds: u32,
+    _padding: u32,
+    /// World position of the grid's lowest node.
+    grid_origin: IVec3,
+    _padding_2: u32,
+    grid_dims: UVec3,
+    _padding_3: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MeshUniforms {
+    view_projection: Mat4,
+    y_clip_min: i32,
+    y_clip_max: i32,
+    _padding: [u32; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderBackend {
+    /// Raymarch the voxel grid in a fragment shader.
+    Raymarch,
+    /// Rasterize a triangle mesh built from the block.
+    Mesh,
+}
+
+impl RenderBackend {
+    pub fn toggled(self) -> Self {
+        match self {
+            RenderBackend::Raymarch => RenderBackend::Mesh,
+            RenderBackend::Mesh => RenderBackend::Raymarch,
+        }
+    }
 }
 
 pub struct Renderer {
-    surface: Surface<'static>,
     adapter: Adapter,
+    /// Size and format of the frames. Offscreen renderers keep it only for those.
     surface_config: SurfaceConfiguration,
     device: Device,
     queue: Queue,
@@ -37,40 +93,174 @@ pub struct Renderer {
     fullscreen_triangle: MeshBuffer,
     bind_group_layout: BindGroupLayout,
     uniform_buffer: Buffer,
+    flood_buffer: Buffer,
+    node_flags_buffer: Buffer,
+    flood_level: Option<i32>,
+    lod_distances: [f32; LOD_LEVELS - 1],
+    emissive_threshold: Option<u8>,
+    y_clip: (Option<i32>, Option<i32>),
+    show_mapchunk_bounds: bool,
+
+    mesh_pipeline: RenderPipeline,
+    mesh_bind_group: BindGroup,
+    mesh_uniform_buffer: Buffer,
+    mesh_shader: ShaderModule,
+    mesh_pipeline_layout: PipelineLayout,
+    /// Depth buffer and multisampled color for the mesh, the size of whatever the scene is
+    /// drawn to.
+    mesh_target: MeshTarget,
+    /// Global ids of the nodes the raymarcher hit, the size of whatever the scene is drawn
+    /// to.
+    node_ids: NodeIdTarget,
+    /// Whether `node_ids` holds the last frame, which it doesn't if the mesh drew it.
+    node_ids_drawn: bool,
 
-    window: Window,
+    blit_pipeline: RenderPipeline,
+    blit_bind_group_layout: BindGroupLayout,
+    blit_sampler: Sampler,
+
+    backend: RenderBackend,
+    render_scale: f32,
+    scaled_target: Option<ScaledTarget>,
+
+    /// `None` when the device cannot write timestamps.
+    gpu_timer: Option<GpuTimer>,
+
+    /// Set by [`Renderer::request_capture`] until the next frame is drawn and read back.
+    capture_requested: bool,
+    captured_frame: Option<RgbaImage>,
+
+    output: Output,
 }
 
-impl Renderer {
-    pub fn new(window: Window) -> Self {
-        let instance = Instance::new(&InstanceDescriptor::default());
+/// Where frames are drawn to.
+enum Output {
+    Window {
+        surface: Surface<'static>,
+        /// Set when the swapchain no longer matches the surface and must be configured
+        /// again before the next frame.
+        outdated: bool,
+        window: Box<Window>,
+        /// Whether frames can be copied out of the surface, for screenshots.
+        copyable: bool,
+    },
+    /// A texture that can be read back with [`Renderer::capture_frame`].
+    Offscreen { texture: Texture },
+}
 
-        // SAFETY: Window has the same lifetime as surface
-        let surface = unsafe {
-            instance
-                .create_surface_unsafe(SurfaceTargetUnsafe::from_window(&window).unwrap())
-                .unwrap()
-        };
+#[derive(thiserror::Error, Debug)]
+pub enum RendererError {
+    #[error(
+        "no compatible GPU adapter found; check the graphics drivers, or pick another backend \
+         with WGPU_BACKEND"
+    )]
+    NoAdapter,
 
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .block_on()
-            .unwrap();
+    #[error("the window has no usable handle: {0}")]
+    WindowHandle(#[from] wgpu::rwh::HandleError),
+
+    #[error("failed to create a surface for the window: {0}")]
+    CreateSurface(#[from] CreateSurfaceError),
+
+    #[error("failed to open the GPU device: {0}")]
+    RequestDevice(#[from] RequestDeviceError),
+
+    #[error("renderer failed to draw a test frame: {0}")]
+    Validation(wgpu::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ScreenshotError {
+    #[error("the surface does not allow copying frames")]
+    Unsupported,
+}
+
+impl Renderer {
+    /// Creates a renderer for `window` on the backends named by `WGPU_BACKEND`, or on any
+    /// backend if it is not set.
+    ///
+    /// Fails rather than panics when no adapter or device fits, the window has no surface,
+    /// or the pipelines can't draw a test frame.
+    pub fn new(window: Window) -> Result<Self, RendererError> {
+        Self::new_with_backends(window, env_backends())
+    }
+
+    /// Creates a renderer for `window`, preferring an adapter on one of `backends`.
+    pub fn new_with_backends(window: Window, backends: Backends) -> Result<Self, RendererError> {
+        let (adapter, surface) = request_adapter(backends, Some(&window))?;
+        let surface = surface.expect("a window always gets a surface");
 
         let inner_size = window.inner_size();
-        let surface_config = surface
+        let mut surface_config = surface
             .get_default_config(&adapter, inner_size.width, inner_size.height)
-            .unwrap();
+            .ok_or(RendererError::NoAdapter)?;
 
-        let (device, queue) = adapter
-            .request_device(&DeviceDescriptor::default())
-            .block_on()
-            .unwrap();
+        // frames are copied out of the surface for screenshots
+        let copyable = surface
+            .get_capabilities(&adapter)
+            .usages
+            .contains(TextureUsages::COPY_SRC);
+        if copyable {
+            surface_config.usage |= TextureUsages::COPY_SRC;
+        }
+
+        let (device, queue) = request_device(&adapter)?;
+
+        let output = Output::Window {
+            surface,
+            outdated: false,
+            window: Box::new(window),
+            copyable,
+        };
+
+        let mut renderer = Self::with_output(adapter, device, queue, surface_config, output)?;
+        renderer.resize(inner_size);
+
+        Ok(renderer)
+    }
+
+    /// Creates a renderer that draws `width`×`height` frames into a texture instead of a
+    /// window, to be read back with [`Renderer::capture_frame`].
+    /// Like [`Renderer::new`], `WGPU_BACKEND` picks the backends to prefer.
+    pub fn new_headless(width: u32, height: u32) -> Result<Self, RendererError> {
+        let (adapter, _) = request_adapter(env_backends(), None)?;
+
+        let (device, queue) = request_device(&adapter)?;
+
+        let surface_config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: OFFSCREEN_FORMAT,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: CompositeAlphaMode::Opaque,
+            view_formats: Vec::new(),
+        };
+
+        let texture = create_frame_texture(
+            &device,
+            OFFSCREEN_FORMAT,
+            surface_config.width,
+            surface_config.height,
+        );
 
+        Self::with_output(
+            adapter,
+            device,
+            queue,
+            surface_config,
+            Output::Offscreen { texture },
+        )
+    }
+
+    fn with_output(
+        adapter: Adapter,
+        device: Device,
+        queue: Queue,
+        surface_config: SurfaceConfiguration,
+        output: Output,
+    ) -> Result<Self, RendererError> {
         let shader = device.create_shader_module(ShaderModuleDescriptor {
             label: None,
             source: ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
@@ -99,6 +289,26 @@ impl Renderer {
                     },
                     count: None,
                 },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -121,11 +331,18 @@ impl Renderer {
                 module: &shader,
                 entry_point: Some("fs_main"),
                 compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: surface_config.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: NODE_ID_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ],
             }),
             primitive: PrimitiveState {
                 topology: PrimitiveTopology::TriangleList,
@@ -183,8 +400,163 @@ impl Renderer {
             mapped_at_creation: false,
         });
 
-        let mut renderer = Self {
-            surface,
+        let flood_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (16 * 16 * 16 * std::mem::size_of::<u32>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // one entry per possible global id
+        let node_flags_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: ((u16::MAX as usize + 1) * std::mem::size_of::<u32>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mesh_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("mesh.wgsl").into()),
+        });
+
+        let mesh_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let mesh_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&mesh_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mesh_pipeline = create_mesh_pipeline(
+            &device,
+            &mesh_pipeline_layout,
+            &mesh_shader,
+            surface_config.format,
+            1,
+        );
+
+        let mesh_uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<MeshUniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mesh_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &mesh_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: mesh_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let blit_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("blit.wgsl").into()),
+        });
+
+        let blit_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let blit_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&blit_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let blit_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&blit_pipeline_layout),
+            vertex: VertexState {
+                module: &blit_shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[vertex_layout()],
+            },
+            fragment: Some(FragmentState {
+                module: &blit_shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let blit_sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mesh_target = MeshTarget::new(
+            &device,
+            surface_config.format,
+            surface_config.width,
+            surface_config.height,
+            1,
+        );
+
+        let node_ids = NodeIdTarget::new(&device, surface_config.width, surface_config.height);
+
+        let gpu_timer = device
+            .features()
+            .contains(Features::TIMESTAMP_QUERY)
+            .then(|| GpuTimer::new(&device, &queue));
+
+        let renderer = Self {
             adapter,
             surface_config,
             device,
@@ -194,13 +566,121 @@ impl Renderer {
             fullscreen_triangle,
             bind_group_layout,
             uniform_buffer,
+            flood_buffer,
+            node_flags_buffer,
+            flood_level: None,
+            lod_distances: [48.0, 96.0],
+            emissive_threshold: None,
+            y_clip: (None, None),
+            show_mapchunk_bounds: false,
 
-            window,
+            mesh_pipeline,
+            mesh_bind_group,
+            mesh_uniform_buffer,
+            mesh_shader,
+            mesh_pipeline_layout,
+            mesh_target,
+            node_ids,
+            node_ids_drawn: false,
+
+            blit_pipeline,
+            blit_bind_group_layout,
+            blit_sampler,
+
+            backend: RenderBackend::Raymarch,
+            render_scale: 1.0,
+            scaled_target: None,
+
+            gpu_timer,
+
+            capture_requested: false,
+            captured_frame: None,
+
+            output,
         };
 
-        renderer.resize(inner_size);
+        renderer.validate().map_err(RendererError::Validation)?;
+
+        Ok(renderer)
+    }
+
+    /// Waits for the GPU to finish, then tears the renderer down with the surface going
+    /// before the device. Plain dropping would release the device first, which some drivers
+    /// report as errors when the window closes.
+    pub fn shutdown(self) {
+        let _ = self.device.poll(PollType::wait_indefinitely());
+
+        drop(self.output);
+    }
+
+    /// Draws a single pixel off-screen with every pipeline and reports the first validation
+    /// error. Pipeline and binding mismatches otherwise only show up on the first frame.
+    pub fn validate(&self) -> Result<(), wgpu::Error> {
+        self.device.push_error_scope(ErrorFilter::Validation);
+
+        let texture = self.device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
 
-        renderer
+        let dims = GridDims::splat(16);
+        let empty_grid = crate::lod::build_pyramid(&vec![0; dims.element_product() as usize], dims);
+        let data = self.create_data_buffer(IVec3::ZERO, dims, bytemuck::cast_slice(&empty_grid));
+        let bind_group = self.raymarch_bind_group(&data);
+        let scaled_target = self.create_scaled_target(1, 1);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+
+        let mesh_target = MeshTarget::new(
+            &self.device,
+            self.surface_config.format,
+            1,
+            1,
+            self.mesh_target.sample_count,
+        );
+
+        let node_ids = NodeIdTarget::new(&self.device, 1, 1);
+
+        for backend in [RenderBackend::Raymarch, RenderBackend::Mesh] {
+            let mesh_target = (backend == RenderBackend::Mesh).then_some(&mesh_target);
+            let node_ids = (backend == RenderBackend::Raymarch).then_some(&node_ids.view);
+            let mut render_pass = begin_pass(&mut encoder, &view, mesh_target, node_ids, None);
+
+            match backend {
+                RenderBackend::Raymarch => {
+                    render_pass.set_pipeline(&self.render_pipeline);
+                    render_pass.set_bind_group(0, &bind_group, &[]);
+                }
+                RenderBackend::Mesh => {
+                    render_pass.set_pipeline(&self.mesh_pipeline);
+                    render_pass.set_bind_group(0, &self.mesh_bind_group, &[]);
+                }
+            }
+
+            self.fullscreen_triangle.draw(&mut render_pass);
+        }
+
+        self.blit(&mut encoder, &scaled_target, &view);
+
+        self.queue.submit([encoder.finish()]);
+
+        match self.device.pop_error_scope().block_on() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
 
     pub fn create_mesh_buffer(&self, mesh: &Mesh) -> MeshBuffer {
@@ -218,14 +698,151 @@ impl Renderer {
         }
     }
 
-    pub fn create_data_buffer(&self, data: &[u8]) -> DataBuffer {
+    /// Uploads a voxel grid of `dims` nodes whose lowest corner is at block `block_pos`,
+    /// together with its coarser detail levels, as built by [`crate::lod::build_pyramid`].
+    pub fn create_data_buffer(&self, block_pos: IVec3, dims: GridDims, data: &[u8]) -> DataBuffer {
         let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
             label: None,
-            contents: &data,
-            usage: BufferUsages::STORAGE,
+            contents: data,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
         });
 
-        DataBuffer { buffer }
+        DataBuffer {
+            buffer,
+            block_pos,
+            dims,
+        }
+    }
+
+    /// Replaces the contents of `buffer` with a new grid, given as for
+    /// [`Renderer::create_data_buffer`]. The data is written into the existing buffer when
+    /// it fits, leaving any bytes past its end as they were; only a larger grid gets a new
+    /// buffer.
+    ///
+    /// `buffer` must come from [`Renderer::create_data_buffer`], which creates it with
+    /// `COPY_DST` usage.
+    pub fn update_data_buffer(
+        &self,
+        buffer: &mut DataBuffer,
+        block_pos: IVec3,
+        dims: GridDims,
+        data: &[u8],
+    ) {
+        if data.len() as u64 > buffer.buffer.size() {
+            *buffer = self.create_data_buffer(block_pos, dims, data);
+            return;
+        }
+
+        self.queue.write_buffer(&buffer.buffer, 0, data);
+        buffer.block_pos = block_pos;
+        buffer.dims = dims;
+    }
+
+    pub fn backend(&self) -> RenderBackend {
+        self.backend
+    }
+
+    pub fn set_backend(&mut self, backend: RenderBackend) {
+        self.backend = backend;
+    }
+
+    /// Renders at `scale` times the window's resolution and stretches the result over the
+    /// window. Values are clamped to (0, 1]; 1 renders straight to the window.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale.clamp(f32::EPSILON, 1.0);
+    }
+
+    /// Shows a water surface at grid height `level`, or hides it when `level` is `None`.
+    ///
+    /// `flooded` marks the grid cells below the surface that hold water, one value per
+    /// cell, as produced by [`crate::flood::flood_fill`].
+    pub fn set_flood_level(&mut self, level: Option<i32>, flooded: &[u32]) {
+        self.flood_level = level;
+
+        if level.is_none() {
+            return;
+        }
+
+        let flooded: &[u8] = bytemuck::cast_slice(flooded);
+        if flooded.len() as u64 > self.flood_buffer.size() {
+            self.flood_buffer = self.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: flooded,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            });
+        } else {
+            self.queue.write_buffer(&self.flood_buffer, 0, flooded);
+        }
+    }
+
+    /// Sets how the raymarcher treats each node, indexed by global id, using the bits from
+    /// [`GlobalMapping::node_flags`](crate::node::GlobalMapping::node_flags).
+    pub fn set_node_flags(&mut self, flags: &[u32]) {
+        self.queue
+            .write_buffer(&self.node_flags_buffer, 0, bytemuck::cast_slice(flags));
+    }
+
+    /// Sets the camera distances at which the raymarcher switches to the next coarser
+    /// level of the grid pyramid.
+    pub fn set_lod_distances(&mut self, distances: [f32; LOD_LEVELS - 1]) {
+        self.lod_distances = distances;
+    }
+
+    /// Renders nodes whose stored artificial light is at least `threshold` as glowing, and
+    /// lets that light tint the faces around them. `None` turns emission off.
+    pub fn set_emissive_threshold(&mut self, threshold: Option<u8>) {
+        self.emissive_threshold = threshold;
+    }
+
+    /// Hides nodes below world Y `min` and above world Y `max`, e.g. to look into caves
+    /// under the terrain. `None` leaves that side open.
+    pub fn set_y_clip(&mut self, min: Option<i32>, max: Option<i32>) {
+        self.y_clip = (min, max);
+    }
+
+    /// Outlines the mapchunks terrain is generated in, to tell mapgen seams from other
+    /// artifacts. Only the raymarcher draws them.
+    pub fn set_show_mapchunk_bounds(&mut self, show: bool) {
+        self.show_mapchunk_bounds = show;
+    }
+
+    pub fn show_mapchunk_bounds(&self) -> bool {
+        self.show_mapchunk_bounds
+    }
+
+    /// Presents frames with `mode`, or with `Fifo` if the surface does not support it.
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        let Output::Window { surface, .. } = &self.output else {
+            return;
+        };
+
+        let capabilities = surface.get_capabilities(&self.adapter);
+
+        // The automatic modes are not listed, and pick a supported mode themselves.
+        let supported = matches!(mode, PresentMode::AutoVsync | PresentMode::AutoNoVsync)
+            || capabilities.present_modes.contains(&mode);
+
+        self.surface_config.present_mode = if supported { mode } else { PresentMode::Fifo };
+        surface.configure(&self.device, &self.surface_config);
+    }
+
+    /// Waits for the display's refresh before presenting each frame, which caps the frame
+    /// rate, or presents frames as soon as they are drawn.
+    pub fn set_vsync(&mut self, on: bool) {
+        let mode = if on {
+            PresentMode::Fifo
+        } else {
+            PresentMode::AutoNoVsync
+        };
+
+        self.set_present_mode(mode);
+    }
+
+    pub fn vsync(&self) -> bool {
+        matches!(
+            self.surface_config.present_mode,
+            PresentMode::Fifo | PresentMode::FifoRelaxed | PresentMode::AutoVsync
+        )
     }
 
     pub fn adapter_info(&self) -> AdapterInfo {
@@ -240,33 +857,514 @@ impl Renderer {
         self.surface_config.width = size.width;
         self.surface_config.height = size.height;
 
-        self.surface.configure(&self.device, &self.surface_config);
+        let format = self.surface_config.format;
+        match &mut self.output {
+            Output::Window { surface, .. } => {
+                surface.configure(&self.device, &self.surface_config);
+            }
+            Output::Offscreen { texture } => {
+                *texture = create_frame_texture(&self.device, format, size.width, size.height);
+            }
+        }
+        self.mesh_target = MeshTarget::new(
+            &self.device,
+            self.surface_config.format,
+            size.width,
+            size.height,
+            self.mesh_target.sample_count,
+        );
+    }
+
+    /// Draws the mesh with `count` samples per pixel to smooth its edges, or with 1 sample
+    /// if the surface or depth format does not support that many. The raymarcher is not
+    /// affected.
+    pub fn set_sample_count(&mut self, count: u32) {
+        // without adapter specific format features, only the counts WebGPU guarantees work
+        let adapter_specific = self
+            .device
+            .features()
+            .contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let formats_support = [self.surface_config.format, DEPTH_FORMAT]
+            .iter()
+            .all(|&format| {
+                self.adapter
+                    .get_texture_format_features(format)
+                    .flags
+                    .sample_count_supported(count)
+            });
+        let supported = formats_support && (adapter_specific || matches!(count, 1 | 4));
+        let count = if supported { count } else { 1 };
+
+        self.mesh_pipeline = create_mesh_pipeline(
+            &self.device,
+            &self.mesh_pipeline_layout,
+            &self.mesh_shader,
+            self.surface_config.format,
+            count,
+        );
+        self.mesh_target = MeshTarget::new(
+            &self.device,
+            self.surface_config.format,
+            self.mesh_target.width,
+            self.mesh_target.height,
+            count,
+        );
+    }
+
+    /// Prints the uniforms the next frame would be rendered with, for debugging.
+    pub fn dump_uniforms(&self, camera: &Camera, data: &DataBuffer) {
+        let uniforms = self.shader_uniforms(camera, data);
+
+        println!("shader uniforms:");
+        println!("  forward: {}", uniforms.forward);
+        println!(
+            "  fov: {}° ({} rad)",
+            uniforms.fov.to_degrees(),
+            uniforms.fov
+        );
+        println!("  position: {}", uniforms.position);
+        println!(
+            "  aspect_ratio: {} ({}x{})",
+            uniforms.aspect_ratio, self.surface_config.width, self.surface_config.height
+        );
+        println!(
+            "  flood_level: {} (enabled: {})",
+            uniforms.flood_level,
+            uniforms.flood_enabled != 0
+        );
+        println!("  lod: {}", uniforms.lod);
+        println!("  emissive_threshold: {}", uniforms.emissive_threshold);
+        println!(
+            "  y_clip: {}..={} (grid)",
+            uniforms.y_clip_min, uniforms.y_clip_max
+        );
+        println!(
+            "  show_mapchunk_bounds: {}",
+            uniforms.show_mapchunk_bounds != 0
+        );
+        println!("  grid_dims: {}", uniforms.grid_dims);
+        println!("  block: {}", data.block_pos);
+    }
+
+    /// Draws a frame. Frames that cannot be acquired right now are skipped; an error is
+    /// returned only when the surface cannot be used anymore, e.g. when out of memory.
+    pub fn render(
+        &mut self,
+        camera: &Camera,
+        data: &DataBuffer,
+        mesh: &MeshBuffer,
+    ) -> Result<(), SurfaceError> {
+        let (surface_texture, view) = match &mut self.output {
+            Output::Window {
+                surface,
+                outdated,
+                window,
+                ..
+            } => {
+                if *outdated {
+                    surface.configure(&self.device, &self.surface_config);
+                    *outdated = false;
+                }
+
+                // Skipped frames are requested again, since the viewer may not draw another
+                // one on its own while nothing changes.
+                let surface_texture = match surface.get_current_texture() {
+                    Ok(surface_texture) => surface_texture,
+                    Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                        *outdated = true;
+                        window.request_redraw();
+                        return Ok(());
+                    }
+                    Err(SurfaceError::Timeout) => {
+                        window.request_redraw();
+                        return Ok(());
+                    }
+                    Err(err) => return Err(err),
+                };
+
+                // A suboptimal texture can still be presented, but keeping the swapchain as
+                // it is slowly degrades presentation on some platforms.
+                if surface_texture.suboptimal {
+                    *outdated = true;
+                }
+
+                let view = surface_texture
+                    .texture
+                    .create_view(&TextureViewDescriptor::default());
+                (Some(surface_texture), view)
+            }
+            Output::Offscreen { texture } => {
+                (None, texture.create_view(&TextureViewDescriptor::default()))
+            }
+        };
+
+        self.draw(&view, camera, data, mesh);
+
+        // the surface texture is gone once presented, so it is read back before that
+        if self.capture_requested {
+            let texture = match (&surface_texture, &self.output) {
+                (Some(surface_texture), _) => &surface_texture.texture,
+                (None, Output::Offscreen { texture }) => texture,
+                (None, Output::Window { .. }) => unreachable!("windows draw to the surface"),
+            };
+            self.captured_frame = Some(self.read_texture(texture));
+            self.capture_requested = false;
+        }
+
+        if let Some(surface_texture) = surface_texture {
+            surface_texture.present();
+        }
+
+        Ok(())
     }
 
-    pub fn render(&mut self, camera: &Camera, data: &DataBuffer) {
+    /// Draws the scene into `surface_texture_view`.
+    fn draw(
+        &mut self,
+        surface_texture_view: &TextureView,
+        camera: &Camera,
+        data: &DataBuffer,
+        mesh: &MeshBuffer,
+    ) {
         let mut encoder = self
             .device
             .create_command_encoder(&CommandEncoderDescriptor::default());
 
-        let surface_texture = self.surface.get_current_texture().unwrap();
-        let surface_texture_view = surface_texture
-            .texture
-            .create_view(&TextureViewDescriptor::default());
+        let uniforms = self.shader_uniforms(camera, data);
+        let aspect_ratio = uniforms.aspect_ratio;
 
-        let (forward, _) = camera.forward_right();
-        let fov = camera.fov.to_radians();
+        let bind_group = self.raymarch_bind_group(data);
 
-        let inner_size = self.window.inner_size();
-        let aspect_ratio = inner_size.width as f32 / inner_size.height as f32;
+        self.queue
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
 
-        let uniforms = ShaderUniforms {
-            forward,
-            fov,
-            position: camera.position,
-            aspect_ratio,
+        // Mesh cubes are centered on node positions, while the raymarched grid places
+        // node (x, y, z) in [x, x + 1), so shift the mesh to line the two up.
+        let mesh_uniforms = MeshUniforms {
+            view_projection: camera.view_projection(aspect_ratio)
+                * Mat4::from_translation(Vec3::splat(0.5)),
+            y_clip_min: uniforms.y_clip_min,
+            y_clip_max: uniforms.y_clip_max,
+            _padding: [0; 2],
+        };
+
+        self.queue.write_buffer(
+            &self.mesh_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[mesh_uniforms]),
+        );
+
+        let scaled_target = (self.render_scale < 1.0).then(|| {
+            let width = (self.surface_config.width as f32 * self.render_scale).ceil() as u32;
+            let height = (self.surface_config.height as f32 * self.render_scale).ceil() as u32;
+
+            match self.scaled_target.take() {
+                Some(target) if (target.width, target.height) == (width, height) => target,
+                _ => self.create_scaled_target(width, height),
+            }
+        });
+
+        let (width, height) = match &scaled_target {
+            Some(target) => (target.width, target.height),
+            None => (self.surface_config.width, self.surface_config.height),
+        };
+        if (self.mesh_target.width, self.mesh_target.height) != (width, height) {
+            self.mesh_target = MeshTarget::new(
+                &self.device,
+                self.surface_config.format,
+                width,
+                height,
+                self.mesh_target.sample_count,
+            );
+        }
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.read(&self.device);
+        }
+
+        if (self.node_ids.width, self.node_ids.height) != (width, height) {
+            self.node_ids = NodeIdTarget::new(&self.device, width, height);
+        }
+        self.node_ids_drawn = self.backend == RenderBackend::Raymarch;
+
+        {
+            let view = match &scaled_target {
+                Some(target) => &target.view,
+                None => surface_texture_view,
+            };
+            // only the mesh pipeline tests depth; the raymarcher sorts hits itself
+            let mesh_target = (self.backend == RenderBackend::Mesh).then_some(&self.mesh_target);
+            let node_ids = (self.backend == RenderBackend::Raymarch).then_some(&self.node_ids.view);
+            let timestamp_writes = self.gpu_timer.as_ref().and_then(GpuTimer::timestamp_writes);
+            let mut render_pass =
+                begin_pass(&mut encoder, view, mesh_target, node_ids, timestamp_writes);
+
+            match self.backend {
+                RenderBackend::Raymarch => {
+                    render_pass.set_pipeline(&self.render_pipeline);
+                    render_pass.set_bind_group(0, &bind_group, &[]);
+
+                    self.fullscreen_triangle.draw(&mut render_pass);
+                }
+                RenderBackend::Mesh => {
+                    render_pass.set_pipeline(&self.mesh_pipeline);
+                    render_pass.set_bind_group(0, &self.mesh_bind_group, &[]);
+
+                    mesh.draw(&mut render_pass);
+                }
+            }
+        }
+
+        if let Some(target) = &scaled_target {
+            self.blit(&mut encoder, target, surface_texture_view);
+        }
+        self.scaled_target = scaled_target;
+
+        if let Some(timer) = &self.gpu_timer {
+            timer.resolve(&mut encoder);
+        }
+
+        self.queue.submit([encoder.finish()]);
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.map();
+        }
+    }
+
+    /// How long the GPU took to draw the scene in a recent frame, or `None` if the device
+    /// cannot measure it. Lags a frame or two behind, as reading the time back never waits.
+    pub fn last_frame_gpu_time(&self) -> Option<Duration> {
+        self.gpu_timer.as_ref()?.last
+    }
+
+    /// Returns the global id of the node shown at pixel (`x`, `y`) of the last frame, or
+    /// `None` if the ray there hit nothing or the frame was drawn with the mesh. Waits for
+    /// the GPU to finish drawing the frame.
+    pub fn read_node_id_at(&self, x: u32, y: u32) -> Option<u16> {
+        if !self.node_ids_drawn || x >= self.surface_config.width || y >= self.surface_config.height
+        {
+            return None;
+        }
+
+        // the scene may be drawn at a lower resolution than the frame
+        let target = &self.node_ids;
+        let x =
+            (u64::from(x) * u64::from(target.width) / u64::from(self.surface_config.width)) as u32;
+        let y = (u64::from(y) * u64::from(target.height) / u64::from(self.surface_config.height))
+            as u32;
+
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: size_of::<u32>() as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture: &target.texture,
+                mip_level: 0,
+                origin: Origin3d { x, y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    // a single row needs no stride
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |result| result.unwrap());
+        self.device.poll(PollType::wait_indefinitely()).unwrap();
+
+        let id = u32::from_le_bytes(slice.get_mapped_range()[..4].try_into().unwrap());
+
+        // air is never hit, so it marks pixels where the ray hit nothing
+        (id != 0).then_some(id as u16)
+    }
+
+    /// Draws `data` from `camera` with the raymarcher, without showing it, and returns the
+    /// global id of the node at `pixel` like [`Renderer::read_node_id_at`] does. Meant for
+    /// scripted queries; the frame shown by the output is left as it is.
+    pub fn sample_node_at(
+        &mut self,
+        camera: &Camera,
+        data: &DataBuffer,
+        pixel: (u32, u32),
+    ) -> Option<u16> {
+        let texture = create_frame_texture(
+            &self.device,
+            self.surface_config.format,
+            self.surface_config.width,
+            self.surface_config.height,
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let mesh = self.create_mesh_buffer(&Mesh::new());
+
+        let backend = std::mem::replace(&mut self.backend, RenderBackend::Raymarch);
+        self.draw(&view, camera, data, &mesh);
+        self.backend = backend;
+
+        self.read_node_id_at(pixel.0, pixel.1)
+    }
+
+    /// Asks for the next frame drawn to be read back, for a screenshot. Frames are only
+    /// copied when asked for, as copying every frame would slow the viewer down for
+    /// nothing. Pick the frame up with [`Renderer::take_captured_frame`] once drawn.
+    pub fn request_capture(&mut self) -> Result<(), ScreenshotError> {
+        if let Output::Window {
+            copyable: false, ..
+        } = self.output
+        {
+            return Err(ScreenshotError::Unsupported);
+        }
+
+        self.capture_requested = true;
+        Ok(())
+    }
+
+    /// Returns the frame asked for with [`Renderer::request_capture`], once it was drawn.
+    pub fn take_captured_frame(&mut self) -> Option<RgbaImage> {
+        self.captured_frame.take()
+    }
+
+    /// Reads back the last frame of a renderer made with [`Renderer::new_headless`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the renderer draws to a window, whose frames are gone once shown; use
+    /// [`Renderer::request_capture`] there.
+    pub fn capture_frame(&self) -> RgbaImage {
+        let Output::Offscreen { texture } = &self.output else {
+            panic!("only offscreen frames can be read back at any time");
         };
 
+        self.read_texture(texture)
+    }
+
+    /// Copies an 8-bit RGBA or BGRA `texture` back from the GPU.
+    fn read_texture(&self, texture: &Texture) -> RgbaImage {
+        let (width, height) = (texture.width(), texture.height());
+
+        // rows of a buffer copy must start at multiples of 256 bytes
+        let row_len = width * 4;
+        let padded_row_len = row_len.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (padded_row_len * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_len),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |result| result.unwrap());
+        self.device.poll(PollType::wait_indefinitely()).unwrap();
+
+        let data = slice.get_mapped_range();
+        let mut image = RgbaImage::new(width, height);
+        for (row, pixels) in data
+            .chunks_exact(padded_row_len as usize)
+            .zip(image.chunks_exact_mut(row_len as usize))
+        {
+            pixels.copy_from_slice(&row[..row_len as usize]);
+        }
+
+        if matches!(
+            texture.format(),
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in image.pixels_mut() {
+                pixel.0.swap(0, 2);
+            }
+        }
+
+        image
+    }
+
+    fn create_scaled_target(&self, width: u32, height: u32) -> ScaledTarget {
+        let texture = self.device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
         let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.blit_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.blit_sampler),
+                },
+            ],
+        });
+
+        ScaledTarget {
+            view,
+            bind_group,
+            width,
+            height,
+        }
+    }
+
+    /// Stretches `source` over `target`.
+    fn blit(&self, encoder: &mut CommandEncoder, source: &ScaledTarget, target: &TextureView) {
+        let mut render_pass = begin_pass(encoder, target, None, None, None);
+
+        render_pass.set_pipeline(&self.blit_pipeline);
+        render_pass.set_bind_group(0, &source.bind_group, &[]);
+
+        self.fullscreen_triangle.draw(&mut render_pass);
+    }
+
+    fn raymarch_bind_group(&self, data: &DataBuffer) -> BindGroup {
+        self.device.create_bind_group(&BindGroupDescriptor {
             label: None,
             layout: &self.bind_group_layout,
             entries: &[
@@ -278,46 +1376,475 @@ impl Renderer {
                     binding: 1,
                     resource: data.buffer.as_entire_binding(),
                 },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.flood_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.node_flags_buffer.as_entire_binding(),
+                },
             ],
+        })
+    }
+
+    fn shader_uniforms(&self, camera: &Camera, data: &DataBuffer) -> ShaderUniforms {
+        let (forward, _) = camera.forward_right();
+
+        let aspect_ratio = self.surface_config.width as f32 / self.surface_config.height as f32;
+        let grid_y = data.block_pos.y * 16;
+
+        ShaderUniforms {
+            forward,
+            fov: camera.fov.to_radians(),
+            position: camera.position,
+            aspect_ratio,
+            flood_level: self.flood_level.unwrap_or(0) as f32,
+            flood_enabled: self.flood_level.is_some() as u32,
+            lod: self.lod_for(camera, data.dims),
+            // light levels never exceed 15, so 16 disables emission
+            emissive_threshold: self.emissive_threshold.map_or(16, u32::from),
+            // the shaders work in grid coordinates, relative to the block's lowest node
+            y_clip_min: self
+                .y_clip
+                .0
+                .map_or(i32::MIN, |min| min.saturating_sub(grid_y)),
+            y_clip_max: self
+                .y_clip
+                .1
+                .map_or(i32::MAX, |max| max.saturating_sub(grid_y)),
+            show_mapchunk_bounds: self.show_mapchunk_bounds as u32,
+            _padding: 0,
+            grid_origin: data.block_pos * 16,
+            _padding_2: 0,
+            grid_dims: data.dims,
+            _padding_3: 0,
+        }
+    }
+
+    fn lod_for(&self, camera: &Camera, dims: GridDims) -> u32 {
+        let distance = camera
+            .position
+            .clamp(Vec3::ZERO, dims.as_vec3())
+            .distance(camera.position);
+
+        self.lod_distances
+            .iter()
+            .take_while(|&&threshold| distance >= threshold)
+            .count() as u32
+    }
+
+    /// The window frames are drawn to, or `None` for offscreen renderers.
+    pub fn window(&self) -> Option<&Window> {
+        match &self.output {
+            Output::Window { window, .. } => Some(window),
+            Output::Offscreen { .. } => None,
+        }
+    }
+}
+
+/// Format of offscreen frames, matching what [`Renderer::capture_frame`] returns.
+const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Creates a texture frames can be drawn into and read back from.
+fn create_frame_texture(
+    device: &Device,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+/// Starts a pass that clears `view` before drawing. With a `mesh_target`, the pass also
+/// clears and tests its depth buffer, and when that is multisampled, draws into its color
+/// texture and resolves the result into `view`. With `node_ids`, the pass also clears that
+/// to air and writes the ids of the nodes drawn to it.
+fn begin_pass<'a>(
+    encoder: &'a mut CommandEncoder,
+    view: &TextureView,
+    mesh_target: Option<&MeshTarget>,
+    node_ids: Option<&TextureView>,
+    timestamp_writes: Option<RenderPassTimestampWrites>,
+) -> RenderPass<'a> {
+    let (view, resolve_target) = match mesh_target.and_then(|target| target.color.as_ref()) {
+        Some(color) => (color, Some(view)),
+        None => (view, None),
+    };
+
+    let color_attachments = [
+        Some(RenderPassColorAttachment {
+            view,
+            depth_slice: None,
+            resolve_target,
+            ops: Operations {
+                load: LoadOp::Clear(Color::BLACK),
+                store: StoreOp::Store,
+            },
+        }),
+        node_ids.map(|view| RenderPassColorAttachment {
+            view,
+            depth_slice: None,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Clear(Color::TRANSPARENT),
+                store: StoreOp::Store,
+            },
+        }),
+    ];
+    // pipelines without a node id target must not see an empty slot for it either
+    let color_attachment_count = if node_ids.is_some() { 2 } else { 1 };
+
+    encoder.begin_render_pass(&RenderPassDescriptor {
+        label: None,
+        color_attachments: &color_attachments[..color_attachment_count],
+        depth_stencil_attachment: mesh_target.map(|target| RenderPassDepthStencilAttachment {
+            view: &target.depth,
+            depth_ops: Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: StoreOp::Discard,
+            }),
+            stencil_ops: None,
+        }),
+        timestamp_writes,
+        occlusion_query_set: None,
+    })
+}
+
+/// The backends named by the `WGPU_BACKEND` environment variable, or all of them.
+fn env_backends() -> Backends {
+    Backends::from_env().unwrap_or(Backends::all())
+}
+
+/// Finds an adapter on one of `backends`, or on any backend if none of those has one.
+/// With a `window`, the adapter must be able to draw to it, and the surface for it is
+/// returned as well.
+fn request_adapter(
+    backends: Backends,
+    window: Option<&Window>,
+) -> Result<(Adapter, Option<Surface<'static>>), RendererError> {
+    let try_backends = |backends| -> Result<_, RendererError> {
+        let instance = Instance::new(&InstanceDescriptor {
+            backends,
+            ..InstanceDescriptor::default()
         });
 
-        self.queue
-            .write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        // SAFETY: Window has the same lifetime as surface
+        let surface = match window {
+            Some(window) => Some(unsafe {
+                instance.create_surface_unsafe(SurfaceTargetUnsafe::from_window(window)?)?
+            }),
+            None => None,
+        };
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &surface_texture_view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(Color::BLACK),
-                        store: StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: surface.as_ref(),
+            })
+            .block_on()
+            .map_err(|_| RendererError::NoAdapter)?;
+
+        Ok((adapter, surface))
+    };
+
+    let (adapter, surface) = try_backends(backends).or_else(|err| {
+        if backends == Backends::all() {
+            return Err(err);
+        }
+
+        eprintln!("no adapter found on backends {backends:?} ({err}), trying all of them");
+        try_backends(Backends::all())
+    })?;
+
+    let info = adapter.get_info();
+    println!("using {} on {}", info.name, info.backend);
+
+    Ok((adapter, surface))
+}
+
+/// Requests a device with the optional features the renderer makes use of.
+fn request_device(adapter: &Adapter) -> Result<(Device, Queue), RequestDeviceError> {
+    adapter
+        .request_device(&DeviceDescriptor {
+            required_features: adapter.features() & Features::TIMESTAMP_QUERY,
+            ..Default::default()
+        })
+        .block_on()
+}
+
+fn create_mesh_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    format: TextureFormat,
+    sample_count: u32,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[vertex_layout()],
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// Measures how long the scene pass takes on the GPU.
+struct GpuTimer {
+    query_set: QuerySet,
+    /// Receives the two timestamps from `query_set`.
+    resolve_buffer: Buffer,
+    /// A mappable copy of `resolve_buffer`.
+    read_buffer: Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    /// Set once `read_buffer` has been mapped.
+    mapped: Arc<AtomicBool>,
+    /// Whether `read_buffer` is being mapped. It cannot be copied into meanwhile, so
+    /// frames drawn until then are not measured.
+    pending: bool,
+    last: Option<Duration>,
+}
+
+impl GpuTimer {
+    const SIZE: u64 = 2 * size_of::<u64>() as u64;
+
+    fn new(device: &Device, queue: &Queue) -> Self {
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: None,
+            ty: QueryType::Timestamp,
+            count: 2,
+        });
+
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: Self::SIZE,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let read_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: Self::SIZE,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            read_buffer,
+            period: queue.get_timestamp_period(),
+            mapped: Arc::new(AtomicBool::new(false)),
+            pending: false,
+            last: None,
+        }
+    }
+
+    /// Where a pass writes its timestamps, or `None` if this frame is not measured.
+    fn timestamp_writes(&self) -> Option<RenderPassTimestampWrites<'_>> {
+        (!self.pending).then_some(RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        })
+    }
+
+    fn resolve(&self, encoder: &mut CommandEncoder) {
+        if self.pending {
+            return;
+        }
+
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.read_buffer, 0, Self::SIZE);
+    }
+
+    fn map(&mut self) {
+        if self.pending {
+            return;
+        }
+
+        let mapped = self.mapped.clone();
+        self.read_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
             });
+        self.pending = true;
+    }
+
+    /// Picks up the timestamps of an earlier frame if they have arrived.
+    fn read(&mut self, device: &Device) {
+        if !self.pending {
+            return;
+        }
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &bind_group, &[]);
+        let _ = device.poll(PollType::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
 
-            render_pass.set_vertex_buffer(0, self.fullscreen_triangle.vertex_buffer.slice(..));
-            render_pass.draw(0..self.fullscreen_triangle.num_vertices, 0..1);
+        {
+            let data = self.read_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            let ticks = timestamps[1].saturating_sub(timestamps[0]);
+            self.last = Some(Duration::from_nanos(
+                (ticks as f64 * f64::from(self.period)) as u64,
+            ));
         }
 
-        self.queue.submit([encoder.finish()]);
+        self.read_buffer.unmap();
+        self.pending = false;
+    }
+}
+
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+/// Format of [`NodeIdTarget`], holding one global node id per pixel.
+const NODE_ID_FORMAT: TextureFormat = TextureFormat::R32Uint;
 
-        surface_texture.present();
+/// Where the raymarcher writes the global id of the node each pixel shows, for picking.
+struct NodeIdTarget {
+    texture: Texture,
+    view: TextureView,
+    width: u32,
+    height: u32,
+}
+
+impl NodeIdTarget {
+    fn new(device: &Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: NODE_ID_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            width,
+            height,
+        }
     }
+}
 
-    pub fn window(&self) -> &Window {
-        &self.window
+/// Attachments the mesh is drawn with, besides the texture it ends up in.
+struct MeshTarget {
+    depth: TextureView,
+    /// Multisampled color texture that is resolved into the final one, if multisampling.
+    color: Option<TextureView>,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+}
+
+impl MeshTarget {
+    fn new(
+        device: &Device,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let create_view = |format| {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: width.max(1),
+                    height: height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+
+            texture.create_view(&TextureViewDescriptor::default())
+        };
+
+        Self {
+            depth: create_view(DEPTH_FORMAT),
+            color: (sample_count > 1).then(|| create_view(format)),
+            width,
+            height,
+            sample_count,
+        }
     }
 }
 
+/// Offscreen texture the scene is drawn to when rendering below the window's resolution.
+struct ScaledTarget {
+    view: TextureView,
+    bind_group: BindGroup,
+    width: u32,
+    height: u32,
+}
+
 pub struct MeshBuffer {
     vertex_buffer: Buffer,
     index_buffer: Option<Buffer>,
@@ -325,6 +1852,20 @@ pub struct MeshBuffer {
     num_vertices: u32,
 }
 
+impl MeshBuffer {
+    fn draw(&self, render_pass: &mut RenderPass) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+
+        match &self.index_buffer {
+            Some(index_buffer) => {
+                render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+                render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            }
+            None => render_pass.draw(0..self.num_vertices, 0..1),
+        }
+    }
+}
+
 const ATTRIBUTES: [VertexAttribute; 3] = [
     VertexAttribute {
         offset: 0,
@@ -353,4 +1894,7 @@ fn vertex_layout() -> VertexBufferLayout<'static> {
 
 pub struct DataBuffer {
     buffer: Buffer,
+    /// Position of the grid's lowest block.
+    block_pos: IVec3,
+    dims: GridDims,
 }
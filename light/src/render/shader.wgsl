@@ -14,10 +14,35 @@ struct Uniforms {
     fov: f32,
     position: vec3f,
     aspect_ratio: f32,
+    flood_level: f32,
+    flood_enabled: u32,
+    lod: u32,
+    emissive_threshold: u32,
+    y_clip_min: i32,
+    y_clip_max: i32,
+    show_mapchunk_bounds: u32,
+    grid_origin: vec3i,
+    grid_dims: vec3u,
 };
 
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
 @group(0) @binding(1) var<storage, read> grid: array<u32>;
+@group(0) @binding(2) var<storage, read> flooded: array<u32>;
+@group(0) @binding(3) var<storage, read> node_flags: array<u32>;
+
+const WATER_COLOR = vec3(0.1, 0.3, 0.8);
+const EMISSIVE_COLOR = vec3(1.0, 0.75, 0.4);
+const SEE_THROUGH_COLOR = vec3(0.7, 0.85, 0.9);
+const MAPCHUNK_BOUNDS_COLOR = vec3(0.2, 1.0, 0.4);
+
+// mapgen works in chunks of 5x5x5 blocks, with the chunk at the origin spanning blocks -2..2
+const MAPCHUNK_SIZE = 80.0;
+const MAPCHUNK_OFFSET = -32.0;
+const MAPCHUNK_LINE_WIDTH = 0.08;
+
+// must match the flags in node.rs
+const NODE_SEE_THROUGH = 1u;
+const NODE_HIDDEN = 2u;
 
 @vertex
 fn vs_main(
@@ -29,14 +54,21 @@ fn vs_main(
     return out;
 }
 
+struct FragmentOutput {
+    @location(0) color: vec4f,
+    // global id of the node hit, or 0 (air) where the ray hit nothing
+    @location(1) node_id: u32,
+};
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+fn fs_main(in: VertexOutput) -> FragmentOutput {
     var ray: Ray;
     ray.origin = uniforms.position;
     ray.dir = get_ray_dir(uniforms.aspect_ratio, in.texcoord);
     ray.inv_dir = 1.0 / ray.dir;
 
-    let box_dist = s_box(ray, vec3f(8, 8, 8), vec3f(8, 8, 8));
+    let half_size = vec3f(uniforms.grid_dims) / 2.0;
+    let box_dist = s_box(ray, half_size, half_size);
     if box_dist > 0.0 {
         ray.origin += ray.dir * (box_dist - 0.1);
     }
@@ -44,17 +76,63 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4f {
     var distance: f32;
     var normal: vec3f;
     var voxel: u32;
+    var water_steps: u32;
+    var see_through_layers: u32;
 
-    let intersects = block_dda(ray, &distance, &normal, &voxel);
+    // coarser detail levels are marched in their own cell units
+    let lod_scale = f32(1u << uniforms.lod);
+    var lod_ray = ray;
+    lod_ray.origin /= lod_scale;
+
+    let intersects = block_dda(lod_ray, &distance, &normal, &voxel, &water_steps, &see_through_layers);
+    distance *= lod_scale;
+    water_steps *= 1u << uniforms.lod;
+
+    var color = vec3(0.0);
 
     if intersects {
         let hit_point = ray.origin + distance * ray.dir;
         let sun_dir = normalize(vec3(0.5, 0.7, 1.0));
         let light = saturate(max(dot(normal, sun_dir), 0.2));
-        return vec4(light, 0.0, 0.0, 1.0);
+        color = vec3(light, 0.0, 0.0);
+
+        if night_light(voxel) >= uniforms.emissive_threshold {
+            color += EMISSIVE_COLOR;
+        } else if uniforms.emissive_threshold <= 15u {
+            // the game already spreads light from sources into neighbouring air nodes,
+            // so the node in front of the hit face tells how much glow reaches it
+            let lod_hit_point = lod_ray.origin + (distance / lod_scale) * lod_ray.dir;
+            let front = fetch_voxel(vec3i(floor(lod_hit_point + normal * 0.5)));
+            let glow = f32(night_light(front)) / 15.0;
+            color += EMISSIVE_COLOR * glow * glow * 0.6;
+        }
+
+        if uniforms.show_mapchunk_bounds != 0u && on_mapchunk_bound(hit_point, normal) {
+            color = MAPCHUNK_BOUNDS_COLOR;
+        }
     }
 
-    return vec4(0.0, 0.0, 0.0, 1.0);
+    // every glass pane or leaf the ray passed through dims what is behind it
+    color = mix(color, SEE_THROUGH_COLOR, 1.0 - pow(0.75, f32(see_through_layers)));
+
+    if uniforms.flood_enabled != 0u {
+        // nodes seen through water fade towards the water color
+        color = mix(color, WATER_COLOR, 1.0 - exp(-0.15 * f32(water_steps)));
+
+        let surface_dist = (uniforms.flood_level - ray.origin.y) * ray.inv_dir.y;
+        if surface_dist > 0.0 && (!intersects || surface_dist < distance) {
+            let surface_point = ray.origin + surface_dist * ray.dir;
+            let cell_below = vec3i(vec3f(floor(surface_point.x), uniforms.flood_level - 1.0, floor(surface_point.z)));
+            if is_flooded(cell_below) {
+                color = mix(color, WATER_COLOR, 0.35);
+            }
+        }
+    }
+
+    var out: FragmentOutput;
+    out.color = vec4(color, 1.0);
+    out.node_id = select(0u, voxel >> 16u, intersects);
+    return out;
 }
 
 struct Ray {
@@ -77,7 +155,6 @@ fn get_ray_dir(aspect_ratio: f32, texcoord: vec2f) -> vec3f {
 }
 
 const BLOCK_SIZE: u32 = 16;
-const BLOCK_DDA_MAX_STEPS: u32 = 48;
 const BLOCK_VOLUME = BLOCK_SIZE * BLOCK_SIZE * BLOCK_SIZE;
 
 const SUPERBLOCK_SIZE: u32 = 8;
@@ -100,7 +177,7 @@ fn superblock_dda(ray: Ray, distance: ptr<function, f32>, normal: ptr<function,
             break;
         }
 
-        if any(dda.voxel_pos > vec3i(i32(BLOCK_SIZE))) || any(dda.voxel_pos < vec3i(-1)) {
+        if any(dda.voxel_pos > vec3i(lod_size())) || any(dda.voxel_pos < vec3i(-1)) {
             break;
         }
     }
@@ -108,23 +185,41 @@ fn superblock_dda(ray: Ray, distance: ptr<function, f32>, normal: ptr<function,
     return intersects;
 }
 
-fn block_dda(ray: Ray, distance: ptr<function, f32>, normal: ptr<function, vec3f>, voxel: ptr<function, u32>) -> bool {
+fn block_dda(ray: Ray, distance: ptr<function, f32>, normal: ptr<function, vec3f>, voxel: ptr<function, u32>, water_steps: ptr<function, u32>, see_through_layers: ptr<function, u32>) -> bool {
     var r = ray;
     var intersects = false;
+    var previous_id = 0u;
 
     var dda = dda_init(r);
 
-    for (var i = 0u; i < BLOCK_DDA_MAX_STEPS; i += 1u) {
+    // a ray crosses at most this many cells on its way through the grid
+    let size = lod_size();
+    let max_steps = size.x + size.y + size.z;
+
+    for (var i = 0u; i < max_steps; i += 1u) {
         dda_step(&dda);
         *voxel = fetch_voxel(dda.voxel_pos);
 
-        let id = ((*voxel >> 24) & 0xFF) | ((*voxel >> 16) & 0xFF);
-        if id != 0u {
-            intersects = true;
-            break;
+        let id = *voxel >> 16;
+        let flags = node_flags[id];
+        if id != 0u && (flags & NODE_HIDDEN) == 0u && !is_clipped(dda.voxel_pos.y) {
+            if (flags & NODE_SEE_THROUGH) == 0u {
+                intersects = true;
+                break;
+            }
+
+            // a run of the same see-through node is a single surface, like a thick window
+            if id != previous_id {
+                *see_through_layers += 1u;
+            }
         }
+        previous_id = id;
 
-        if any(dda.voxel_pos > vec3i(i32(BLOCK_SIZE))) || any(dda.voxel_pos < vec3i(-1)) {
+        if is_flooded(dda.voxel_pos * i32(1u << uniforms.lod)) {
+            *water_steps += 1u;
+        }
+
+        if any(dda.voxel_pos > vec3i(size)) || any(dda.voxel_pos < vec3i(-1)) {
             break;
         }
     }
@@ -175,9 +270,53 @@ fn dda_end(dda: DDAState, ray: Ray, distance: ptr<function, f32>, normal: ptr<fu
     *distance = max(mini.x, max(mini.y, mini.z));
 }
 
+// param1 keeps sunlight in the low nibble and artificial light in the high one
+fn night_light(voxel: u32) -> u32 {
+    return (voxel >> 12) & 0xF;
+}
+
+// whether a point on a face lies on a line where the face crosses a mapchunk boundary
+fn on_mapchunk_bound(grid_point: vec3f, normal: vec3f) -> bool {
+    let chunk_point = grid_point + vec3f(uniforms.grid_origin) - MAPCHUNK_OFFSET;
+    let distance = abs(chunk_point - round(chunk_point / MAPCHUNK_SIZE) * MAPCHUNK_SIZE);
+
+    // a face lying in a boundary plane isn't a line; only the other two axes count
+    let along_face = select(distance, vec3(MAPCHUNK_SIZE), abs(normal) > vec3(0.5));
+    return min(along_face.x, min(along_face.y, along_face.z)) < MAPCHUNK_LINE_WIDTH;
+}
+
+// a coarser cell counts as clipped when its lowest layer of nodes is
+fn is_clipped(lod_y: i32) -> bool {
+    let y = lod_y * i32(1u << uniforms.lod);
+    return y < uniforms.y_clip_min || y > uniforms.y_clip_max;
+}
+
+fn lod_size() -> vec3u {
+    return uniforms.grid_dims >> vec3u(uniforms.lod);
+}
+
+// detail levels are stored one after another, from finest to coarsest
+fn lod_offset(lod: u32) -> u32 {
+    var offset = 0u;
+    for (var i = 0u; i < lod; i += 1u) {
+        let size = uniforms.grid_dims >> vec3u(i);
+        offset += size.x * size.y * size.z;
+    }
+    return offset;
+}
+
 fn fetch_voxel(pos: vec3i) -> u32 {
-    let in_bounds = all(pos < vec3i(BLOCK_SIZE)) && all(pos >= vec3i(0));
-    return select(0u, grid[u32(pos.x) + u32(pos.y) * BLOCK_SIZE + u32(pos.z) * BLOCK_SIZE * BLOCK_SIZE], in_bounds);
+    let size = lod_size();
+    let in_bounds = all(pos < vec3i(size)) && all(pos >= vec3i(0));
+    let index = lod_offset(uniforms.lod) + u32(pos.x) + u32(pos.y) * size.x + u32(pos.z) * size.x * size.y;
+    return select(0u, grid[index], in_bounds);
+}
+
+fn is_flooded(pos: vec3i) -> bool {
+    let size = uniforms.grid_dims;
+    let in_bounds = all(pos < vec3i(size)) && all(pos >= vec3i(0));
+    let index = u32(pos.x) + u32(pos.y) * size.x + u32(pos.z) * size.x * size.y;
+    return uniforms.flood_enabled != 0u && in_bounds && select(0u, flooded[index], in_bounds) != 0u;
 }
 
 // http://iquilezles.org/www/articles/boxfunctions/boxfunctions.htm
@@ -0,0 +1,149 @@
+use glam::Vec3;
+
+use crate::camera::CameraPose;
+
+/// A camera path through recorded keyframes, for flythroughs.
+///
+/// Positions follow a Catmull-Rom spline through the keyframes, so the path passes through
+/// every recorded pose without corners; orientations are interpolated with slerp.
+pub struct Tour {
+    keyframes: Vec<Keyframe>,
+}
+
+struct Keyframe {
+    time: f32,
+    pose: CameraPose,
+}
+
+impl Tour {
+    pub fn new() -> Self {
+        Self {
+            keyframes: Vec::new(),
+        }
+    }
+
+    /// Adds a keyframe `time` seconds into the tour. Keyframes must be added in time order.
+    pub fn add_keyframe(&mut self, time: f32, pose: CameraPose) {
+        assert!(
+            self.keyframes.last().is_none_or(|last| last.time < time),
+            "keyframes must be added in time order"
+        );
+
+        self.keyframes.push(Keyframe { time, pose });
+    }
+
+    pub fn len(&self) -> usize {
+        self.keyframes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// Time of the last keyframe, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Returns the pose at `t` seconds. Times outside the tour clamp to the first or last
+    /// keyframe. Returns `None` if there are no keyframes.
+    pub fn sample(&self, t: f32) -> Option<CameraPose> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+
+        if t <= first.time {
+            return Some(first.pose);
+        }
+
+        if t >= last.time {
+            return Some(last.pose);
+        }
+
+        // The segment [i, i + 1] containing t; the neighbours on either side shape the curve
+        // and are clamped at the ends of the tour.
+        let i = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= t)
+            - 1;
+        let k1 = &self.keyframes[i];
+        let k2 = &self.keyframes[i + 1];
+        let k0 = &self.keyframes[i.saturating_sub(1)];
+        let k3 = &self.keyframes[(i + 2).min(self.keyframes.len() - 1)];
+
+        let u = (t - k1.time) / (k2.time - k1.time);
+
+        Some(CameraPose {
+            position: catmull_rom(
+                k0.pose.position,
+                k1.pose.position,
+                k2.pose.position,
+                k3.pose.position,
+                u,
+            ),
+            rotation: k1.pose.rotation.slerp(k2.pose.rotation, u),
+        })
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, vec3};
+
+    use super::*;
+
+    fn pose(x: f32, yaw: f32) -> CameraPose {
+        CameraPose {
+            position: vec3(x, 2.0 * x, -x),
+            rotation: Quat::from_rotation_y(yaw),
+        }
+    }
+
+    fn tour() -> Tour {
+        let mut tour = Tour::new();
+        tour.add_keyframe(0.0, pose(0.0, 0.0));
+        tour.add_keyframe(1.0, pose(4.0, 0.5));
+        tour.add_keyframe(3.0, pose(5.0, 1.0));
+        tour.add_keyframe(4.0, pose(10.0, 2.0));
+        tour
+    }
+
+    fn assert_pose_eq(actual: CameraPose, expected: CameraPose) {
+        assert!(
+            actual.position.abs_diff_eq(expected.position, 1e-5)
+                && actual.rotation.abs_diff_eq(expected.rotation, 1e-5),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn sample_passes_through_keyframes() {
+        let tour = tour();
+
+        for keyframe in &tour.keyframes {
+            assert_pose_eq(tour.sample(keyframe.time).unwrap(), keyframe.pose);
+        }
+    }
+
+    #[test]
+    fn sample_clamps_outside_the_tour() {
+        let tour = tour();
+
+        assert_eq!(tour.sample(-1.0), Some(pose(0.0, 0.0)));
+        assert_eq!(tour.sample(100.0), Some(pose(10.0, 2.0)));
+    }
+
+    #[test]
+    fn sample_of_an_empty_tour_is_none() {
+        assert_eq!(Tour::new().sample(0.0), None);
+    }
+}
@@ -0,0 +1,123 @@
+use glam::{IVec3, Vec3, ivec3};
+
+/// Height of the camera above the ground when walking, about a player's eye height.
+pub const EYE_HEIGHT: f32 = 1.7;
+
+/// Half the width of the body that collides with walls.
+const RADIUS: f32 = 0.3;
+
+/// Ledges up to this high are stepped onto instead of blocking the way, so stairs and
+/// single nodes can be walked over.
+const STEP_HEIGHT: f32 = 1.0;
+
+/// How far below the camera to look for ground.
+const MAX_DROP: i32 = 64;
+
+/// Moves a walking camera at `eye` by the horizontal part of `delta`, stopping at walls,
+/// and puts it back at eye height above the ground it ends up over.
+///
+/// Each axis moves on its own, so the camera slides along a wall it walks into at an angle.
+/// Where there is no ground within reach, the camera keeps its height.
+pub fn walk(eye: Vec3, delta: Vec3, is_solid: impl Fn(IVec3) -> bool) -> Vec3 {
+    let mut eye = eye;
+
+    for axis in [0, 2] {
+        let mut next = eye;
+        next[axis] += delta[axis];
+
+        if !collides(next, &is_solid) {
+            eye = next;
+        }
+    }
+
+    snap_to_ground(eye, &is_solid)
+}
+
+/// Puts the camera at eye height above the highest solid node under its footprint.
+pub fn snap_to_ground(eye: Vec3, is_solid: impl Fn(IVec3) -> bool) -> Vec3 {
+    let (min, max) = footprint(eye);
+    let top = eye.y.floor() as i32;
+
+    for y in (top - MAX_DROP..=top).rev() {
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                if is_solid(ivec3(x, y, z)) {
+                    return Vec3::new(eye.x, (y + 1) as f32 + EYE_HEIGHT, eye.z);
+                }
+            }
+        }
+    }
+
+    eye
+}
+
+/// Whether the body above step height overlaps a solid node.
+fn collides(eye: Vec3, is_solid: impl Fn(IVec3) -> bool) -> bool {
+    let (min, max) = footprint(eye);
+    let feet = eye.y - EYE_HEIGHT;
+    let bottom = (feet + STEP_HEIGHT).floor() as i32;
+    let top = eye.y.floor() as i32;
+
+    (bottom..=top)
+        .any(|y| (min.z..=max.z).any(|z| (min.x..=max.x).any(|x| is_solid(ivec3(x, y, z)))))
+}
+
+/// Nodes under the body, as the lowest and highest corner on the XZ plane.
+fn footprint(eye: Vec3) -> (IVec3, IVec3) {
+    let min = Vec3::new(eye.x - RADIUS, 0.0, eye.z - RADIUS)
+        .floor()
+        .as_ivec3();
+    let max = Vec3::new(eye.x + RADIUS, 0.0, eye.z + RADIUS)
+        .floor()
+        .as_ivec3();
+
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+
+    /// Eye position standing on the node at `x`, `z` whose top is at `ground`.
+    fn standing(x: f32, ground: i32, z: f32) -> Vec3 {
+        vec3(x, ground as f32 + EYE_HEIGHT, z)
+    }
+
+    #[test]
+    fn steps_onto_a_ledge() {
+        let ledge = |pos: IVec3| pos.y <= 0 || (pos.x >= 2 && pos.y == 1);
+
+        let eye = walk(standing(1.5, 1, 0.5), vec3(1.0, 0.0, 0.0), ledge);
+
+        assert_eq!(eye, standing(2.5, 2, 0.5));
+    }
+
+    #[test]
+    fn stops_at_a_wall() {
+        let wall = |pos: IVec3| pos.y <= 0 || (pos.x >= 2 && pos.y <= 2);
+
+        let eye = walk(standing(1.5, 1, 0.5), vec3(1.0, 0.0, 0.0), wall);
+
+        assert_eq!(eye, standing(1.5, 1, 0.5));
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let wall = |pos: IVec3| pos.y <= 0 || (pos.x >= 2 && pos.y <= 2);
+
+        let eye = walk(standing(1.5, 1, 0.5), vec3(1.0, 0.0, 1.0), wall);
+
+        assert_eq!(eye, standing(1.5, 1, 1.5));
+    }
+
+    #[test]
+    fn keeps_height_without_ground() {
+        let deep = |pos: IVec3| pos.y < -MAX_DROP - 10;
+
+        let eye = walk(vec3(0.5, 10.0, 0.5), vec3(1.0, 0.0, 0.0), deep);
+
+        assert_eq!(eye, vec3(1.5, 10.0, 0.5));
+    }
+}